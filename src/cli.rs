@@ -0,0 +1,964 @@
+use crate::color::ColorChoice;
+use crate::logger;
+use crate::page::{CommentStyle, LineEnding, PAGE_FUNCTIONS};
+use crate::template::PageType;
+use anyhow::{anyhow, bail, Result};
+use std::collections::VecDeque;
+use std::env;
+use std::path::PathBuf;
+
+pub const EXIT_STATUS: [(&str, &str); 4] = [
+    ("    0    ", "Success, no page needed to change"),
+    (
+        "    1    ",
+        "Pages were changed, or would be with check, diff or --dry-run",
+    ),
+    (
+        "    2    ",
+        "A page could not be parsed or migrated, or the usage was wrong",
+    ),
+    ("    3    ", "A file could not be read or written"),
+];
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum Command {
+    #[default]
+    Migrate,
+    New,
+    Check,
+    List,
+    Diff,
+    Detect,
+    Stats,
+    Rename,
+    Delete,
+    Undo,
+    Tui,
+    Template,
+    Man,
+}
+
+impl Command {
+    pub const ALL: [Self; 13] = [
+        Self::Migrate,
+        Self::New,
+        Self::Check,
+        Self::List,
+        Self::Diff,
+        Self::Detect,
+        Self::Stats,
+        Self::Rename,
+        Self::Delete,
+        Self::Undo,
+        Self::Tui,
+        Self::Template,
+        Self::Man,
+    ];
+
+    pub fn from(string: &str) -> Option<Self> {
+        match string {
+            "migrate" => Some(Self::Migrate),
+            "new" => Some(Self::New),
+            "check" => Some(Self::Check),
+            "list" => Some(Self::List),
+            "diff" => Some(Self::Diff),
+            "detect" => Some(Self::Detect),
+            "stats" => Some(Self::Stats),
+            "rename" => Some(Self::Rename),
+            "delete" => Some(Self::Delete),
+            "undo" => Some(Self::Undo),
+            "tui" => Some(Self::Tui),
+            "template" => Some(Self::Template),
+            "man" => Some(Self::Man),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Migrate => "migrate",
+            Self::New => "new",
+            Self::Check => "check",
+            Self::List => "list",
+            Self::Diff => "diff",
+            Self::Detect => "detect",
+            Self::Stats => "stats",
+            Self::Rename => "rename",
+            Self::Delete => "delete",
+            Self::Undo => "undo",
+            Self::Tui => "tui",
+            Self::Template => "template",
+            Self::Man => "man",
+        }
+    }
+
+    /// Whether the command renders pages from a template, and hence accepts a
+    /// TEMPLATE argument and the template flags.
+    pub fn templated(self) -> bool {
+        matches!(
+            self,
+            Self::Migrate | Self::New | Self::Check | Self::Diff | Self::Tui | Self::Template
+        )
+    }
+
+    /// Whether the command works on many files at once.
+    fn batched(self) -> bool {
+        matches!(self, Self::Migrate | Self::Check | Self::Diff)
+    }
+
+    /// Whether the command prints or writes pages.
+    fn writes(self) -> bool {
+        matches!(
+            self,
+            Self::Migrate | Self::New | Self::Diff | Self::Tui | Self::Template
+        )
+    }
+
+    /// Whether the command reports on many files at once.
+    fn listed(self) -> bool {
+        matches!(self, Self::List | Self::Stats | Self::Tui)
+    }
+
+    pub fn usage(self) -> &'static str {
+        match self {
+            Self::Migrate => "[migrate] [FLAG]... [OPTION]... [PATH]... [TEMPLATE]",
+            Self::New => "new [FLAG]... PATH|ROUTE TEMPLATE",
+            Self::Check => "check [FLAG]... [OPTION]... [PATH]... [TEMPLATE]",
+            Self::List => "list [FLAG]... [PATH]...",
+            Self::Diff => "diff [FLAG]... [OPTION]... [PATH]... [TEMPLATE]",
+            Self::Detect => "detect [FLAG]... PATH",
+            Self::Stats => "stats [FLAG]... [PATH]...",
+            Self::Rename => "rename [FLAG]... FROM TO",
+            Self::Delete => "delete [FLAG]... PATH",
+            Self::Undo => "undo [FLAG]... [PATH]",
+            Self::Tui => "tui [FLAG]... [OPTION]... [PATH]... [TEMPLATE]",
+            Self::Template => "template [FLAG]... [OPTION]... TEMPLATE",
+            Self::Man => "man",
+        }
+    }
+
+    pub fn about(self) -> &'static str {
+        match self {
+            Self::Migrate => "Migrate pages to the given template",
+            Self::New => "Create a new page from the given template",
+            Self::Check => "Exit with an error if any page would change",
+            Self::List => "List pages and their types",
+            Self::Diff => "Print the changes a migration would make",
+            Self::Detect => "Print the type of the given page",
+            Self::Stats => "Summarize the migration progress of the pages",
+            Self::Rename => "Move a page and rename its module",
+            Self::Delete => "Delete a page and report what still uses it",
+            Self::Undo => "Restore a page to how it was before its last migration",
+            Self::Tui => "Preview and migrate pages in a terminal UI",
+            Self::Template => "Print a blank page of the given template",
+            Self::Man => "Print the manual page in roff format",
+        }
+    }
+
+    pub fn flags(self) -> Vec<(&'static str, &'static str)> {
+        let mut flags = vec![(
+            "    --                 ",
+            "Denotes the end of command-line flags and options",
+        )];
+
+        if self.templated() {
+            flags.extend(vec![
+                (
+                    "    -s  --shared       ",
+                    "Pass the shared model to the page functions",
+                ),
+                (
+                    "    -r  --request      ",
+                    "Pass the request object to the page functions",
+                ),
+                (
+                    "        --with-query   ",
+                    "Start init with an example of reading the query\n                       \
+                    parameters of the request. Implies --request",
+                ),
+                (
+                    "    -u  --user         ",
+                    "Pass the signed in Auth.User to the page functions,\n                       \
+                    binding it in page is up to you without --protected",
+                ),
+                (
+                    "        --protected    ",
+                    "Make it a Page.protected page, for signed in\n                       \
+                    users only, passing the Auth.User to the page\n                       \
+                    functions",
+                ),
+            ]);
+        }
+
+        if self == Self::Migrate {
+            flags.extend(vec![
+                (
+                    "        --dry-run      ",
+                    "Print the result without overwriting file",
+                ),
+                (
+                    "        --diff         ",
+                    "Print a unified diff without overwriting file",
+                ),
+                (
+                    "    -y, --yes          ",
+                    "Don't ask before rewriting each of many files",
+                ),
+                (
+                    "        --watch        ",
+                    "Keep migrating the PATHs marked with\n                       \
+                    '-- elm-spa-migrate: watch' as they change.\n                       \
+                    PATHs default to src/Pages",
+                ),
+                (
+                    "        --annotate-provenance\n                       ",
+                    "With --dry-run, mark where each block came from",
+                ),
+            ]);
+        }
+
+        if self == Self::Check {
+            flags.push((
+                "        --fmt-check    ",
+                "Check that the pages print back exactly as they\n                       \
+                are, instead of checking their type",
+            ));
+            flags.push((
+                "        --check-idempotent\n                       ",
+                "Check that migrating the pages a second time\n                       \
+                changes nothing, instead of checking their type",
+            ));
+        }
+
+        if self == Self::Migrate || self == Self::Tui {
+            flags.push((
+                "        --backup       ",
+                "Copy each file to FILE.bak before overwriting it",
+            ));
+        }
+
+        if self == Self::Migrate || self == Self::Diff {
+            flags.extend(vec![
+                (
+                    "        --no-pager     ",
+                    "Don't page long previews through $PAGER or less",
+                ),
+                (
+                    "    -k, --keep-going   ",
+                    "Write the files that could be migrated even if\n                       \
+                    others failed, then list the failures",
+                ),
+                (
+                    "        --fail-fast    ",
+                    "Stop at the first file that fails to migrate",
+                ),
+                (
+                    "        --fix-module-name\n                       ",
+                    "Rename modules whose name doesn't match their path",
+                ),
+            ]);
+        }
+
+        if matches!(self, Self::Migrate | Self::Diff | Self::Tui) {
+            flags.push((
+                "        --preserve-bodies\n                       ",
+                "Keep the bodies of init, update, view and\n                       \
+                subscriptions, only rewriting their type\n                       \
+                annotation and arguments",
+            ));
+            flags.push((
+                "        --remove-old   ",
+                "Delete the replaced functions instead of leaving\n                       \
+                them commented out",
+            ));
+            flags.push((
+                "        --old-code-at-end",
+                "\n                       Move the commented out functions to the end\n                       \
+                of the page, instead of below their replacements",
+            ));
+            flags.push((
+                "        --old-code-to-sidecar",
+                "\n                       Write the replaced functions to a\n                       \
+                <PAGE>.migrated.orig file next to the page,\n                       \
+                instead of commenting them out",
+            ));
+        }
+
+        if self.batched() {
+            flags.push((
+                "        --strict       ",
+                "Fail on anything that can't be parsed instead of\n                       \
+                keeping it as is with a warning",
+            ));
+        }
+
+        if self.batched() || self == Self::Detect {
+            flags.push((
+                "        --pick         ",
+                "Fuzzy find the page to work on among the PATHs",
+            ));
+        }
+
+        if self == Self::Rename {
+            flags.push((
+                "        --update-references\n                       ",
+                "Also update the modules that refer to the page",
+            ));
+        }
+
+        if self == Self::New || self == Self::Rename {
+            flags.push((
+                "        --force        ",
+                "Overwrite the file if it already exists",
+            ));
+        } else if self.templated() && self != Self::Template {
+            flags.push((
+                "        --force        ",
+                "Migrate even if the file doesn't look like a page,\n                       \
+                or is read-only",
+            ));
+        }
+
+        flags.extend(vec![
+            (
+                "    -v, --verbose      ",
+                "Explain what is being done. Repeat for more details",
+            ),
+            ("    -q, --quiet        ", "Don't print warnings"),
+            ("    -h, --help         ", "Print help information"),
+            ("    -V, --version      ", "Print version information"),
+        ]);
+        flags
+    }
+
+    pub fn options(self) -> Vec<(&'static str, &'static str)> {
+        let mut options = vec![
+            (
+                "        --color <WHEN> ",
+                "When to use colors - auto|always|never.\n                       \
+            Auto mode respects the NO_COLOR environment variable",
+            ),
+            (
+                "        --log-file <FILE>\n                       ",
+                "Write a detailed log with timings to FILE",
+            ),
+            (
+                "        --warnings <FORMAT>\n                       ",
+                "How to print warnings and errors - text|json.\n                       \
+            JSON prints one object per line, with the level,\n                       \
+            file, line and message",
+            ),
+        ];
+
+        if self.batched() || self.listed() {
+            options.push((
+                "        --exclude <PATTERN>\n                       ",
+                "Skip the files matching the glob pattern, e.g.\n                       \
+                'src/Pages/Internal/**'. Can be repeated",
+            ));
+        }
+
+        if self.batched() {
+            options.push((
+                "        --files-from <FILE>\n                       ",
+                "Also migrate the paths listed in FILE, one per line.\n                       \
+                Use - to read the list from stdin",
+            ));
+            if self != Self::Check {
+                options.push((
+                    "    -j, --jobs <N>     ",
+                    "Parse and migrate N files in parallel. Defaults to 1",
+                ));
+            }
+            options.push((
+                "        --only <TYPES> ",
+                "Only process pages currently of the given comma\n                       \
+                separated types, e.g. static,sandbox",
+            ));
+        }
+        if matches!(self, Self::Migrate | Self::Diff | Self::Tui) {
+            options.push((
+                "        --only-fns <NAMES>\n                       ",
+                "Only migrate the given comma separated page\n                       \
+                functions, e.g. page,subscriptions, leaving the\n                       \
+                others as they are",
+            ));
+            options.push((
+                "        --skip-fns <NAMES>\n                       ",
+                "Leave the given comma separated page functions\n                       \
+                as they are, e.g. view, migrating the others",
+            ));
+            options.push((
+                "        --comment-style <STYLE>\n                       ",
+                "Comment out the replaced functions with - line,\n                       \
+                one -- per line, or block, a single {- -}.\n                       \
+                Defaults to line",
+            ));
+        }
+        if matches!(self, Self::Migrate | Self::Diff | Self::Tui | Self::Stats) {
+            options.push((
+                "        --marker <PREFIX>\n                       ",
+                "Comment out the replaced functions with PREFIX\n                       \
+                instead of --, e.g. '-- MIGRATE(2024-06-01):'",
+            ));
+        }
+        if self == Self::Migrate || self == Self::Diff {
+            options.push((
+                "        --from <TYPE>  ",
+                "Fail on pages that aren't currently of the given\n                       \
+                type, read from their Page.<type> call",
+            ));
+        }
+        if self == Self::Migrate {
+            options.push((
+                "    -o, --output <FILE>\n                       ",
+                "Write the migrated page to FILE instead of\n                       \
+                overwriting it. Expects a single page",
+            ));
+            options.push((
+                "        --output-dir <DIR>\n                       ",
+                "Write the migrated pages under DIR, mirroring\n                       \
+                their paths relative to the current directory",
+            ));
+        }
+
+        if self == Self::Migrate || self == Self::Diff {
+            options.push((
+                "        --diff-tool <COMMAND>\n                       ",
+                "Review each migration with COMMAND instead of\n                       \
+                writing it, e.g. 'meld {old} {new}'",
+            ));
+        }
+
+        if self == Self::Migrate || self == Self::Tui {
+            options.push((
+                "        --backup-suffix <SUFFIX>\n                       ",
+                "Use SUFFIX instead of .bak. Implies --backup",
+            ));
+        }
+
+        if self.writes() {
+            options.push((
+                "        --line-ending <ENDING>\n                       ",
+                "Line endings to write - lf|crlf|native. Defaults\n                       \
+                to the ones a page already uses",
+            ));
+        }
+
+        if self == Self::Template {
+            options.push((
+                "        --module <NAME>\n                       ",
+                "Module name of the page. Defaults to Pages.Home_",
+            ));
+        }
+        options
+    }
+
+    pub fn args(self) -> Vec<(&'static str, &'static str)> {
+        let path = match self {
+            Self::Migrate | Self::Check | Self::Diff => (
+                "    <PATH>...     ",
+                "Page to migrate, a directory of pages, or a glob\n                    \
+                pattern like 'src/Pages/**/*.elm'.\n                    \
+                Use - to read from stdin and write to stdout",
+            ),
+            Self::New => (
+                "    <PATH|ROUTE>  ",
+                "Path of the page to create, or a route like\n                    \
+                '/users/:id' to create it under src/Pages",
+            ),
+            Self::List | Self::Stats | Self::Tui => (
+                "    <PATH>...     ",
+                "Pages, directories of pages, or glob patterns.\n                    \
+                Defaults to src/Pages",
+            ),
+            Self::Detect => ("    <PATH>        ", "Page to inspect"),
+            Self::Delete => ("    <PATH>        ", "Path or route of the page to delete"),
+            Self::Undo => (
+                "    <PATH>        ",
+                "Path or route of the page to restore.\n                    \
+                Defaults to the last migrated page",
+            ),
+            Self::Rename => (
+                "    <FROM> <TO>   ",
+                "Current and new path or route of the page",
+            ),
+            Self::Template => {
+                return vec![(
+                    "    <TEMPLATE>    ",
+                    "Specify the page template.\n                    \
+                    Options are - static|element|sandbox|advanced",
+                )]
+            }
+            Self::Man => return vec![],
+        };
+
+        if self == Self::Tui {
+            vec![
+                path,
+                (
+                    "    <TEMPLATE>    ",
+                    "Specify the initial target page template.\n                    \
+                    Options are - static|element|sandbox|advanced.\n                    \
+                    Defaults to element",
+                ),
+            ]
+        } else if self.templated() {
+            vec![
+                path,
+                (
+                    "    <TEMPLATE>    ",
+                    "Specify the target page template.\n                    \
+                    Options are - static|element|sandbox|advanced.\n                    \
+                    Asked interactively if omitted on a terminal",
+                ),
+            ]
+        } else {
+            vec![path]
+        }
+    }
+
+    pub fn help(self) -> String {
+        let section = |items: Vec<(&str, &str)>| -> String {
+            items
+                .into_iter()
+                .map(|(k, v)| format!("\n{}{}", k, v))
+                .collect()
+        };
+
+        let mut help = format!(
+            "{} {}\n{}\n{}\n\nUSAGE:\n    {} {}",
+            env!("CARGO_PKG_NAME"),
+            env!("CARGO_PKG_VERSION"),
+            env!("CARGO_PKG_AUTHORS"),
+            env!("CARGO_PKG_DESCRIPTION"),
+            env!("CARGO_PKG_NAME"),
+            self.usage(),
+        );
+
+        if self == Self::Migrate {
+            help.push_str("\n\nSUBCOMMANDS:");
+            for cmd in Self::ALL {
+                help.push_str(&format!("\n    {:<14}{}", cmd.name(), cmd.about()));
+            }
+        }
+
+        help.push_str(&format!("\n\nFLAGS:{}", section(self.flags())));
+
+        help.push_str(&format!("\n\nOPTIONS:{}", section(self.options())));
+
+        if !self.args().is_empty() {
+            help.push_str(&format!("\n\nARGS:{}", section(self.args())));
+        }
+
+        if self.batched() {
+            help.push_str(&format!(
+                "\n\nEXIT STATUS:{}",
+                section(EXIT_STATUS.to_vec())
+            ));
+        }
+        help.trim().into()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Cli {
+    pub command: Command,
+    pub version: bool,
+    pub help: bool,
+    pub verbose: u8,
+    pub quiet: bool,
+    pub dry_run: bool,
+    pub diff: bool,
+    pub annotate_provenance: bool,
+    pub fmt_check: bool,
+    pub check_idempotent: bool,
+    pub strict: bool,
+    pub fix_module_name: bool,
+    pub preserve_bodies: bool,
+    pub remove_old: bool,
+    pub old_code_at_end: bool,
+    pub old_code_to_sidecar: bool,
+    pub comment_style: CommentStyle,
+    pub marker: Option<String>,
+    pub force: bool,
+    pub update_references: bool,
+    pub pick: bool,
+    pub no_pager: bool,
+    pub keep_going: bool,
+    pub fail_fast: bool,
+    pub yes: bool,
+    pub backup: bool,
+    pub backup_suffix: Option<String>,
+    pub watch: bool,
+    pub shared: bool,
+    pub request: bool,
+    pub protected: bool,
+    pub user: bool,
+    pub with_query: bool,
+    pub color: ColorChoice,
+    pub warnings: logger::Format,
+    pub files_from: Option<PathBuf>,
+    pub jobs: usize,
+    pub module: Option<String>,
+    pub log_file: Option<PathBuf>,
+    pub output: Option<PathBuf>,
+    pub output_dir: Option<PathBuf>,
+    pub diff_tool: Option<String>,
+    pub line_ending: Option<LineEnding>,
+    pub only: Vec<PageType>,
+    pub from: Option<PageType>,
+    pub only_fns: Vec<String>,
+    pub skip_fns: Vec<String>,
+    pub exclude: Vec<glob::Pattern>,
+    pub paths: Vec<PathBuf>,
+    pub template: Option<PageType>,
+}
+
+/// The page functions in a comma separated list, e.g. `page,view`.
+fn functions(option: &str, list: &str) -> Result<Vec<String>> {
+    let mut functions = vec![];
+    for name in list.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+        if !PAGE_FUNCTIONS.contains(&name) {
+            bail!(
+                "{} expects function names among {}, got '{}'",
+                option,
+                PAGE_FUNCTIONS.join("|"),
+                name
+            );
+        }
+        functions.push(name.to_string());
+    }
+    if functions.is_empty() {
+        bail!("{} requires a value", option);
+    }
+    Ok(functions)
+}
+
+impl Cli {
+    pub fn parse(args: env::Args) -> Result<Self> {
+        let mut args: VecDeque<String> = args.skip(1).collect();
+        let mut cli = Self::default();
+
+        if let Some(command) = args.front().and_then(|a| Command::from(a)) {
+            cli.command = command;
+            cli.diff = command == Command::Diff;
+            args.pop_front();
+        }
+
+        let command = cli.command;
+
+        while let Some(arg) = args.pop_front() {
+            match arg.as_str() {
+                // Flags
+                "-h" | "--help" => {
+                    cli.help = true;
+                }
+
+                "-V" | "--version" => {
+                    cli.version = true;
+                }
+
+                "-v" | "--verbose" => cli.verbose += 1,
+
+                "-vv" => cli.verbose += 2,
+
+                "-vvv" => cli.verbose += 3,
+
+                "-q" | "--quiet" => cli.quiet = true,
+
+                "--" => {
+                    cli.paths.extend(args.drain(..).map(PathBuf::from));
+                }
+
+                "-s" | "--shared" if command.templated() => cli.shared = true,
+
+                "-r" | "--request" if command.templated() => cli.request = true,
+
+                "-u" | "--user" if command.templated() => cli.user = true,
+
+                "--with-query" if command.templated() => {
+                    cli.request = true;
+                    cli.with_query = true;
+                }
+
+                "--protected" if command.templated() => cli.protected = true,
+
+                "--dry-run" if command == Command::Migrate => cli.dry_run = true,
+
+                "--diff" if command == Command::Migrate => cli.diff = true,
+
+                "--no-pager" if command == Command::Migrate || command == Command::Diff => {
+                    cli.no_pager = true
+                }
+
+                "--watch" if command == Command::Migrate => cli.watch = true,
+
+                "-y" | "--yes" if command == Command::Migrate => cli.yes = true,
+
+                "-k" | "--keep-going"
+                    if command == Command::Migrate || command == Command::Diff =>
+                {
+                    cli.keep_going = true;
+                    cli.fail_fast = false;
+                }
+
+                "--fail-fast" if command == Command::Migrate || command == Command::Diff => {
+                    cli.fail_fast = true;
+                    cli.keep_going = false;
+                }
+
+                "--backup" if command == Command::Migrate || command == Command::Tui => {
+                    cli.backup = true
+                }
+
+                "--annotate-provenance" if command == Command::Migrate => {
+                    cli.annotate_provenance = true
+                }
+
+                "--fmt-check" if command == Command::Check => cli.fmt_check = true,
+                "--check-idempotent" if command == Command::Check => cli.check_idempotent = true,
+
+                "--strict" if command.batched() => cli.strict = true,
+
+                "--fix-module-name" if command == Command::Migrate || command == Command::Diff => {
+                    cli.fix_module_name = true
+                }
+
+                "--preserve-bodies"
+                    if matches!(command, Command::Migrate | Command::Diff | Command::Tui) =>
+                {
+                    cli.preserve_bodies = true
+                }
+
+                "--remove-old"
+                    if matches!(command, Command::Migrate | Command::Diff | Command::Tui) =>
+                {
+                    cli.remove_old = true
+                }
+
+                "--old-code-at-end"
+                    if matches!(command, Command::Migrate | Command::Diff | Command::Tui) =>
+                {
+                    cli.old_code_at_end = true
+                }
+
+                "--old-code-to-sidecar"
+                    if matches!(command, Command::Migrate | Command::Diff | Command::Tui) =>
+                {
+                    cli.old_code_to_sidecar = true
+                }
+
+                "--force"
+                    if (command.templated() && command != Command::Template)
+                        || command == Command::Rename =>
+                {
+                    cli.force = true
+                }
+
+                "--pick" if command.batched() || command == Command::Detect => cli.pick = true,
+
+                "--update-references" if command == Command::Rename => cli.update_references = true,
+
+                // Options
+                "--color" => {
+                    let when = args.pop_front().unwrap_or_default();
+                    cli.color = ColorChoice::from(&when).ok_or_else(|| {
+                        anyhow!("--color expects one of auto|always|never, got '{}'", when)
+                    })?;
+                }
+
+                arg if arg.starts_with("--color=") => {
+                    let when = arg.trim_start_matches("--color=");
+                    cli.color = ColorChoice::from(when).ok_or_else(|| {
+                        anyhow!("--color expects one of auto|always|never, got '{}'", when)
+                    })?;
+                }
+
+                "--warnings" => {
+                    let format = args.pop_front().unwrap_or_default();
+                    cli.warnings = logger::Format::from(&format).ok_or_else(|| {
+                        anyhow!("--warnings expects one of text|json, got '{}'", format)
+                    })?;
+                }
+
+                arg if arg.starts_with("--warnings=") => {
+                    let format = arg.trim_start_matches("--warnings=");
+                    cli.warnings = logger::Format::from(format).ok_or_else(|| {
+                        anyhow!("--warnings expects one of text|json, got '{}'", format)
+                    })?;
+                }
+
+                "--line-ending" if command.writes() => {
+                    let ending = args.pop_front().unwrap_or_default();
+                    cli.line_ending = Some(LineEnding::from(&ending).ok_or_else(|| {
+                        anyhow!(
+                            "--line-ending expects one of lf|crlf|native, got '{}'",
+                            ending
+                        )
+                    })?);
+                }
+
+                "--log-file" => {
+                    cli.log_file = args.pop_front().map(PathBuf::from);
+                    if cli.log_file.is_none() {
+                        bail!("--log-file requires a value");
+                    }
+                }
+
+                "--files-from" if command.batched() => {
+                    cli.files_from = args.pop_front().map(PathBuf::from);
+                    if cli.files_from.is_none() {
+                        bail!("--files-from requires a value");
+                    }
+                }
+
+                "-o" | "--output" if command == Command::Migrate => {
+                    cli.output = args.pop_front().map(PathBuf::from);
+                    if cli.output.is_none() {
+                        bail!("{} requires a value", arg);
+                    }
+                }
+
+                "--backup-suffix" if command == Command::Migrate || command == Command::Tui => {
+                    cli.backup = true;
+                    cli.backup_suffix = args.pop_front();
+                    if cli.backup_suffix.is_none() {
+                        bail!("--backup-suffix requires a value");
+                    }
+                }
+
+                "--diff-tool" if command == Command::Migrate || command == Command::Diff => {
+                    cli.diff_tool = args.pop_front();
+                    if cli.diff_tool.is_none() {
+                        bail!("--diff-tool requires a value");
+                    }
+                }
+
+                "--output-dir" if command == Command::Migrate => {
+                    cli.output_dir = args.pop_front().map(PathBuf::from);
+                    if cli.output_dir.is_none() {
+                        bail!("--output-dir requires a value");
+                    }
+                }
+
+                "--module" if command == Command::Template => {
+                    cli.module = args.pop_front();
+                    if cli.module.is_none() {
+                        bail!("--module requires a value");
+                    }
+                }
+
+                "-j" | "--jobs" if command == Command::Migrate || command == Command::Diff => {
+                    let jobs = args.pop_front().unwrap_or_default();
+                    cli.jobs = jobs.parse().ok().filter(|&n| n > 0).ok_or_else(|| {
+                        anyhow!("{} expects a positive number, got '{}'", arg, jobs)
+                    })?;
+                }
+
+                "--only" if command.batched() => {
+                    let types = args.pop_front().unwrap_or_default();
+                    for t in types.split(',').map(str::trim).filter(|t| !t.is_empty()) {
+                        let pagetype = PageType::from(t).ok_or_else(|| {
+                            anyhow!(
+                                "--only expects page types among static|sandbox|element|advanced, got '{}'",
+                                t
+                            )
+                        })?;
+                        cli.only.push(pagetype);
+                    }
+                    if cli.only.is_empty() {
+                        bail!("--only requires a value");
+                    }
+                }
+
+                "--only-fns"
+                    if matches!(command, Command::Migrate | Command::Diff | Command::Tui) =>
+                {
+                    let list = args.pop_front().unwrap_or_default();
+                    cli.only_fns.extend(functions(&arg, &list)?);
+                }
+
+                "--skip-fns"
+                    if matches!(command, Command::Migrate | Command::Diff | Command::Tui) =>
+                {
+                    let list = args.pop_front().unwrap_or_default();
+                    cli.skip_fns.extend(functions(&arg, &list)?);
+                }
+
+                "--comment-style"
+                    if matches!(command, Command::Migrate | Command::Diff | Command::Tui) =>
+                {
+                    let style = args.pop_front().unwrap_or_default();
+                    cli.comment_style = CommentStyle::from(&style).ok_or_else(|| {
+                        anyhow!("--comment-style expects one of line|block, got '{}'", style)
+                    })?;
+                }
+
+                "--marker"
+                    if matches!(
+                        command,
+                        Command::Migrate | Command::Diff | Command::Tui | Command::Stats
+                    ) =>
+                {
+                    let marker = args.pop_front().unwrap_or_default();
+                    if !marker.starts_with("--") || marker.contains('\n') {
+                        bail!("--marker expects a line comment, got '{}'", marker);
+                    }
+                    cli.marker = Some(marker);
+                }
+
+                "--from" if command == Command::Migrate || command == Command::Diff => {
+                    let from = args.pop_front().unwrap_or_default();
+                    cli.from = Some(PageType::from(&from).ok_or_else(|| {
+                        anyhow!(
+                            "--from expects one of static|sandbox|element|advanced, got '{}'",
+                            from
+                        )
+                    })?);
+                }
+
+                "--exclude" if command.batched() || command.listed() => {
+                    let pattern = args
+                        .pop_front()
+                        .ok_or_else(|| anyhow!("--exclude requires a value"))?;
+                    cli.exclude.push(glob::Pattern::new(&pattern)?);
+                }
+
+                arg if arg.starts_with('-') && arg != "-" => {
+                    bail!(
+                        "unexpected argument {} for {}\nTry '{} {} --help' for more information.",
+                        arg,
+                        command.name(),
+                        env!("CARGO_PKG_NAME"),
+                        command.name(),
+                    );
+                }
+
+                // paths and template
+                arg => cli.paths.push(arg.into()),
+            }
+        }
+
+        // The last positional argument is the template.
+        if command.templated()
+            && (cli.paths.len() > 1
+                || cli.files_from.is_some()
+                || cli.pick
+                || cli.watch
+                || command == Command::Tui
+                || command == Command::Template)
+        {
+            cli.template = cli
+                .paths
+                .last()
+                .and_then(|p| p.to_str())
+                .and_then(PageType::from);
+            if cli.template.is_some() {
+                cli.paths.pop();
+            }
+        }
+
+        Ok(cli)
+    }
+}
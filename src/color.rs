@@ -0,0 +1,182 @@
+use crate::logger;
+use std::env;
+use std::io::IsTerminal;
+use std::path::Path;
+
+pub const RED: &str = "31";
+pub const GREEN: &str = "32";
+pub const YELLOW: &str = "33";
+pub const BLUE: &str = "34";
+pub const MAGENTA: &str = "35";
+pub const CYAN: &str = "36";
+pub const BOLD: &str = "1";
+pub const DIM: &str = "2";
+
+const KEYWORDS: [&str; 15] = [
+    "module", "exposing", "import", "as", "type", "alias", "port", "case", "of", "let", "in", "if",
+    "then", "else", "effect",
+];
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum ColorChoice {
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    pub fn from(string: &str) -> Option<Self> {
+        match string {
+            "auto" => Some(Self::Auto),
+            "always" => Some(Self::Always),
+            "never" => Some(Self::Never),
+            _ => None,
+        }
+    }
+
+    /// In auto mode, colors are used only on terminals and only if `NO_COLOR`
+    /// is not set. See https://no-color.org.
+    fn enabled(self, tty: bool) -> bool {
+        match self {
+            Self::Always => true,
+            Self::Never => false,
+            Self::Auto => tty && env::var_os("NO_COLOR").is_none_or(|v| v.is_empty()),
+        }
+    }
+
+    pub fn stdout(self) -> bool {
+        self.enabled(std::io::stdout().is_terminal())
+    }
+
+    pub fn stderr(self) -> bool {
+        self.enabled(std::io::stderr().is_terminal())
+    }
+}
+
+pub fn paint(enabled: bool, style: &str, text: &str) -> String {
+    if enabled {
+        format!("\x1b[{}m{}\x1b[0m", style, text)
+    } else {
+        text.into()
+    }
+}
+
+/// Color the lines of a unified diff.
+pub fn diff(enabled: bool, text: &str) -> String {
+    if !enabled {
+        return text.into();
+    }
+
+    text.lines()
+        .map(|line| {
+            let style = if line.starts_with("---") || line.starts_with("+++") {
+                BOLD
+            } else if line.starts_with("@@") {
+                CYAN
+            } else if line.starts_with('-') {
+                RED
+            } else if line.starts_with('+') {
+                GREEN
+            } else {
+                return format!("{}\n", line);
+            };
+            format!("{}\n", paint(true, style, line))
+        })
+        .collect()
+}
+
+/// Highlight the keywords, types, strings and comments of Elm code.
+pub fn elm(enabled: bool, text: &str) -> String {
+    if !enabled {
+        return text.into();
+    }
+
+    let mut out = String::with_capacity(text.len() * 2);
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        let (len, style) = if rest.starts_with("--") {
+            (rest.find('\n').unwrap_or(rest.len()), Some(DIM))
+        } else if rest.starts_with("{-") {
+            (
+                rest.find("-}").map(|i| i + 2).unwrap_or(rest.len()),
+                Some(DIM),
+            )
+        } else if c == '"' || c == '\'' {
+            let end = rest[1..]
+                .char_indices()
+                .scan(false, |escaped, (i, q)| {
+                    let done = q == c && !*escaped;
+                    *escaped = q == '\\' && !*escaped;
+                    Some((i, done))
+                })
+                .find(|(_, done)| *done)
+                .map(|(i, _)| i + 1 + c.len_utf8())
+                .unwrap_or(rest.len());
+            (end, Some(GREEN))
+        } else if c.is_alphabetic() || c == '_' {
+            let len = rest
+                .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            let word = &rest[..len];
+            if KEYWORDS.contains(&word) {
+                (len, Some(MAGENTA))
+            } else if c.is_uppercase() {
+                (len, Some(BLUE))
+            } else {
+                (len, None)
+            }
+        } else {
+            (c.len_utf8(), None)
+        };
+
+        let (token, tail) = rest.split_at(len);
+        match style {
+            // Keep the escapes within lines so that pagers don't mind
+            Some(style) => {
+                let lines: Vec<String> = token.split('\n').map(|l| paint(true, style, l)).collect();
+                out.push_str(&lines.join("\n"));
+            }
+            None => out.push_str(token),
+        }
+        rest = tail;
+    }
+    out
+}
+
+pub fn warning(choice: ColorChoice, message: &str) {
+    logger::record(log::Level::Warn, message);
+    if logger::printing(log::Level::Warn) && logger::json_output() {
+        eprintln!("{}", logger::json(log::Level::Warn, None, None, message));
+    } else if logger::printing(log::Level::Warn) {
+        eprintln!("{} {}", paint(choice.stderr(), YELLOW, "warning:"), message);
+    }
+}
+
+/// Warn about the file, or a line of it.
+pub fn warning_at(choice: ColorChoice, file: &Path, line: Option<usize>, message: &str) {
+    if logger::json_output() {
+        logger::record(log::Level::Warn, message);
+        if logger::printing(log::Level::Warn) {
+            eprintln!(
+                "{}",
+                logger::json(log::Level::Warn, Some(file), line, message)
+            );
+        }
+        return;
+    }
+
+    match line {
+        Some(line) => warning(choice, &format!("{}:{}: {}", file.display(), line, message)),
+        None => warning(choice, &format!("{}: {}", file.display(), message)),
+    }
+}
+
+pub fn error(choice: ColorChoice, message: &str) {
+    logger::record(log::Level::Error, message);
+    if logger::json_output() {
+        eprintln!("{}", logger::json(log::Level::Error, None, None, message));
+    } else {
+        eprintln!("{} {}", paint(choice.stderr(), RED, "error:"), message);
+    }
+}
@@ -0,0 +1,295 @@
+//! Rewriting what `init` and `update` result in, when their bodies are kept
+//! while going between sandbox, element and advanced pages.
+//!
+//! The results are the expressions a body evaluates to, going down into the
+//! branches of `case` and `if` expressions and past `let` blocks. From a
+//! sandbox page, each result gets paired with `Cmd.none` or `Effect.none`.
+//! Between element and advanced pages, only the second element of the
+//! `( model, cmd )` tuples changes. Commands are wrapped in `Effect.fromCmd`,
+//! and `Cmd.none` becomes `Effect.none`. Going back, `Effect.fromCmd` is
+//! unwrapped, and `Effect.none` and `Effect.batch` become `Cmd.none` and
+//! `Cmd.batch`.
+
+use crate::syntax::{self, Token};
+use std::ops::Range;
+
+/// Index of the keyword closing the one at the start, e.g. the `in` of a
+/// `let`, skipping over those nested in between.
+fn closing(tokens: &[Token], open: &str, close: &str, also: &str) -> Option<usize> {
+    let depth = tokens.first()?.depth;
+    let mut nested = 0;
+    for (i, token) in tokens.iter().enumerate().skip(1) {
+        if token.depth != depth {
+            continue;
+        }
+        match token.text {
+            t if t == close && nested == 0 => return Some(i),
+            t if t == close || t == also => nested -= 1,
+            t if t == open => nested += 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// The column of the byte in its line.
+fn column(text: &str, offset: usize) -> usize {
+    offset - text[..offset].rfind('\n').map_or(0, |i| i + 1)
+}
+
+/// Whether the byte is the first one of its line that isn't whitespace.
+fn starts_line(text: &str, offset: usize) -> bool {
+    text[offset - column(text, offset)..offset]
+        .trim()
+        .is_empty()
+}
+
+/// Collect the byte ranges of the results of the expression.
+fn collect(text: &str, tokens: &[Token], results: &mut Vec<Range<usize>>) {
+    let (Some(first), Some(last)) = (tokens.first(), tokens.last()) else {
+        return;
+    };
+
+    match first.text {
+        "let" => {
+            if let Some(i) = closing(tokens, "let", "in", "") {
+                collect(text, &tokens[i + 1..], results);
+            }
+        }
+
+        "if" => {
+            let Some(then) = closing(tokens, "if", "then", "else") else {
+                return;
+            };
+            let Some(r#else) = closing(&tokens[then..], "if", "else", "").map(|i| then + i) else {
+                return;
+            };
+            collect(text, &tokens[then + 1..r#else], results);
+            collect(text, &tokens[r#else + 1..], results);
+        }
+
+        "case" => {
+            let Some(of) = closing(tokens, "case", "of", "") else {
+                return;
+            };
+            let Some(pattern) = tokens.get(of + 1) else {
+                return;
+            };
+            let indent = column(text, pattern.span.start);
+            let branches: Vec<usize> = (of + 1..tokens.len())
+                .filter(|&i| {
+                    let start = tokens[i].span.start;
+                    column(text, start) == indent && starts_line(text, start)
+                })
+                .collect();
+
+            for (n, &start) in branches.iter().enumerate() {
+                let end = branches.get(n + 1).copied().unwrap_or(tokens.len());
+                let arrow = tokens[start..end]
+                    .iter()
+                    .position(|t| t.text == "->" && t.depth == first.depth);
+                if let Some(arrow) = arrow {
+                    collect(text, &tokens[start + arrow + 1..end], results);
+                }
+            }
+        }
+
+        _ => results.push(first.span.start..last.span.end),
+    }
+}
+
+/// Byte ranges of the expressions the body results in.
+fn results(body: &str) -> Vec<Range<usize>> {
+    let mut results = vec![];
+    collect(body, &syntax::tokens(body), &mut results);
+    results
+}
+
+/// The range without the whitespace around it.
+fn trimmed(text: &str, range: Range<usize>) -> Range<usize> {
+    let slice = &text[range.clone()];
+    let start = range.start + slice.len() - slice.trim_start().len();
+    let end = range.end - (slice.len() - slice.trim_end().len());
+    start..end.max(start)
+}
+
+/// Whether the expression is wrapped in a single pair of parentheses.
+fn parenthesized(expression: &str) -> bool {
+    syntax::parens(expression).first() == Some(&(0..expression.len()))
+}
+
+/// Replace the parts of the body, in order.
+fn replaced(body: &str, replacements: Vec<(Range<usize>, String)>) -> String {
+    let mut out = String::with_capacity(body.len());
+    let mut last = 0;
+    for (range, replacement) in replacements {
+        out.push_str(&body[last..range.start]);
+        out.push_str(&replacement);
+        last = range.end;
+    }
+    out.push_str(&body[last..]);
+    out
+}
+
+/// Replace the second element of each tuple the body results in, where the
+/// conversion gives a replacement.
+fn rewrite(body: &str, convert: impl Fn(&str) -> Option<String>) -> String {
+    let replacements = results(body)
+        .into_iter()
+        .filter(|r| parenthesized(&body[r.clone()]))
+        .filter_map(|r| {
+            let inner = r.start + 1..r.end - 1;
+            let parts = syntax::split(&body[inner.clone()], ',');
+            let [_, second] = parts.as_slice() else {
+                return None;
+            };
+            let second = trimmed(body, inner.start + second.start..inner.start + second.end);
+            convert(&body[second.clone()]).map(|c| (second, c))
+        })
+        .collect();
+    replaced(body, replacements)
+}
+
+/// Pair each result of the body with the command, e.g. `( model, Cmd.none )`.
+/// A result spanning many lines gets its tuple laid out the way elm-format
+/// does, indenting the lines after the first.
+fn pair(body: &str, cmd: &str) -> String {
+    let replacements = results(body)
+        .into_iter()
+        .map(|r| {
+            let result = &body[r.clone()];
+            if !result.contains('\n') {
+                return (r, format!("( {}, {} )", result, cmd));
+            }
+            let indent = " ".repeat(column(body, r.start));
+            let result = if result.contains("\"\"\"") {
+                result.to_string()
+            } else {
+                result.replace('\n', "\n  ").replace("\n  \n", "\n\n")
+            };
+            let paired = format!("( {}\n{}, {}\n{})", result, indent, cmd, indent);
+            (r, paired)
+        })
+        .collect();
+    replaced(body, replacements)
+}
+
+/// The command as an effect.
+fn to_effect(cmd: &str) -> Option<String> {
+    let literal = cmd.starts_with(|c: char| c.is_ascii_digit() || "\"'[{".contains(c));
+    match cmd {
+        "Cmd.none" => Some("Effect.none".into()),
+        "" | "()" => None,
+        _ if literal || cmd.starts_with("Effect.") => None,
+        _ if parenthesized(cmd) || !cmd.contains(char::is_whitespace) => {
+            Some(format!("Effect.fromCmd {}", cmd))
+        }
+        _ => Some(format!("Effect.fromCmd ({})", cmd)),
+    }
+}
+
+/// The effect as a command, if it only wraps commands.
+fn to_cmd(effect: &str) -> Option<String> {
+    if effect == "Effect.none" {
+        return Some("Cmd.none".into());
+    }
+
+    if let Some(cmd) = effect
+        .strip_prefix("Effect.fromCmd")
+        .filter(|rest| rest.starts_with(|c: char| c.is_whitespace() || c == '('))
+    {
+        let cmd = cmd.trim_start();
+        let cmd = cmd.strip_prefix("<|").unwrap_or(cmd).trim_start();
+        if parenthesized(cmd) {
+            return Some(cmd[1..cmd.len() - 1].trim().into());
+        }
+        return Some(cmd.into());
+    }
+
+    let list = effect.strip_prefix("Effect.batch")?.trim_start();
+    let inner = list.strip_prefix('[')?.strip_suffix(']')?;
+    let mut out = String::from("Cmd.batch [");
+    let mut last = 0;
+    for part in syntax::split(inner, ',') {
+        let part = trimmed(inner, part);
+        if part.is_empty() {
+            continue;
+        }
+        out.push_str(&inner[last..part.start]);
+        out.push_str(&to_cmd(&inner[part.clone()])?);
+        last = part.end;
+    }
+    out.push_str(&inner[last..]);
+    out.push(']');
+    Some(out)
+}
+
+/// Rewrite the body of an element or advanced page function for a sandbox
+/// page, keeping only the models of the tuples it results in.
+pub fn without_cmds(name: &str, body: &str) -> String {
+    let mut replacements = vec![];
+    for r in results(body) {
+        let result = &body[r.clone()];
+        let inner = Some(result)
+            .filter(|r| parenthesized(r))
+            .map(|r| &r[1..r.len() - 1]);
+        let parts = inner.map(|inner| syntax::split(inner, ','));
+        let (Some(inner), Some([model, cmd])) = (inner, parts.as_deref()) else {
+            log::warn!(
+                "can't tell the model in what {} results in, rewrite `{}` by hand",
+                name,
+                result.lines().next().unwrap_or_default()
+            );
+            continue;
+        };
+
+        let cmd = inner[cmd.clone()].trim();
+        if !matches!(cmd, "Cmd.none" | "Effect.none") {
+            log::warn!(
+                "{} no longer sends `{}`, sandbox pages can't",
+                name,
+                cmd.lines().next().unwrap_or_default()
+            );
+        }
+        // Undo the layout of a tuple spanning many lines
+        let model = inner[model.clone()].trim().replace("\n  ", "\n");
+        replacements.push((r, model));
+    }
+    replaced(body, replacements)
+}
+
+/// Rewrite the body of a sandbox page function for an element page.
+pub fn with_cmds(name: &str, body: &str) -> String {
+    log::info!("paired the results of {} with Cmd.none", name);
+    pair(body, "Cmd.none")
+}
+
+/// Rewrite the body of a sandbox page function for an advanced page.
+pub fn with_effects(name: &str, body: &str) -> String {
+    log::info!("paired the results of {} with Effect.none", name);
+    pair(body, "Effect.none")
+}
+
+/// Rewrite the body of an element page function for an advanced page.
+pub fn to_effects(name: &str, body: &str) -> String {
+    let rewritten = rewrite(body, to_effect);
+    if rewritten != body {
+        log::info!("wrapped the commands of {} into effects", name);
+    }
+    rewritten
+}
+
+/// Rewrite the body of an advanced page function for an element page.
+pub fn to_cmds(name: &str, body: &str) -> String {
+    let rewritten = rewrite(body, to_cmd);
+    if rewritten != body {
+        log::info!("unwrapped the effects of {} into commands", name);
+    }
+    if rewritten.contains("Effect.") {
+        log::warn!(
+            "{} still uses Effect, which element pages can't, change it to use Cmd",
+            name
+        );
+    }
+    rewritten
+}
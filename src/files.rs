@@ -0,0 +1,158 @@
+use anyhow::{bail, Result};
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+
+fn is_glob(path: &str) -> bool {
+    path.contains(['*', '?', '['])
+}
+
+fn is_elm(path: &Path) -> bool {
+    path.extension().map(|e| e == "elm").unwrap_or(false)
+}
+
+fn walk(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            walk(&path, files)?;
+        } else if is_elm(&path) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Expand the PATH argument into the list of files to migrate.
+///
+/// PATH can be a file, a directory (searched recursively for `.elm` files), or
+/// a glob pattern like `src/Pages/Admin/**/*.elm`. Globs are expanded here so
+/// that they work the same way on shells that don't expand them.
+pub fn discover(path: &Path) -> Result<Vec<PathBuf>> {
+    if is_stdin(path) {
+        return Ok(vec![path.into()]);
+    }
+
+    let pattern = path.to_string_lossy();
+
+    let mut files = if !path.exists() && is_glob(&pattern) {
+        let mut files = vec![];
+        for entry in glob::glob(&pattern)? {
+            let entry = entry?;
+            if entry.is_dir() {
+                walk(&entry, &mut files)?;
+            } else {
+                files.push(entry);
+            }
+        }
+        if files.is_empty() {
+            bail!("no files matched pattern {}", pattern);
+        }
+        files
+    } else if path.is_dir() {
+        let mut files = vec![];
+        walk(path, &mut files)?;
+        if files.is_empty() {
+            bail!("no elm files found in {}", path.display());
+        }
+        files
+    } else {
+        vec![path.into()]
+    };
+
+    files.sort();
+    files.dedup();
+    Ok(files)
+}
+
+/// Read newline separated paths from a file, or from stdin if it is `-`.
+pub fn read_files_from(list: &Path) -> Result<Vec<PathBuf>> {
+    let text = if is_stdin(list) {
+        let mut text = String::new();
+        std::io::stdin().read_to_string(&mut text)?;
+        text
+    } else {
+        std::fs::read_to_string(list)?
+    };
+
+    let mut paths = vec![];
+    for (num, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let path = PathBuf::from(line);
+        if !path.is_file() {
+            bail!(
+                "{}:{}: {} is not a file",
+                list.display(),
+                num + 1,
+                path.display()
+            );
+        }
+        paths.push(path);
+    }
+
+    if paths.is_empty() {
+        bail!("no paths found in {}", list.display());
+    }
+    Ok(paths)
+}
+
+pub fn is_stdin(path: &Path) -> bool {
+    path == Path::new("-")
+}
+
+/// Whether the path matches one of the `--exclude` patterns. A pattern without
+/// wildcards excludes the path itself and, if it's a directory, everything in
+/// it.
+pub fn is_excluded(path: &Path, patterns: &[glob::Pattern]) -> bool {
+    let options = glob::MatchOptions {
+        require_literal_separator: true,
+        ..Default::default()
+    };
+    let path = path.strip_prefix(".").unwrap_or(path);
+
+    patterns.iter().any(|pattern| {
+        let literal = Path::new(pattern.as_str());
+        let literal = literal.strip_prefix(".").unwrap_or(literal);
+        pattern.matches_path_with(path, options) || path.starts_with(literal)
+    })
+}
+
+/// Replace the content of the file without ever leaving it half written, by
+/// writing to a temporary file next to it and renaming that over it. The file
+/// keeps its permissions, read-only included.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> Result<()> {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let temp = path.with_file_name(format!(".{}.{}.tmp", name, std::process::id()));
+
+    let result = (|| -> Result<()> {
+        let mut file = std::fs::File::create(&temp)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+        let permissions = std::fs::metadata(path).map(|m| m.permissions()).ok();
+        if let Some(permissions) = &permissions {
+            std::fs::set_permissions(&temp, permissions.clone())?;
+        }
+        // Windows refuses to replace a read-only file, unlike Unix where
+        // only the directory needs to be writable
+        #[cfg(windows)]
+        if let Some(permissions) = permissions.as_ref().filter(|p| p.readonly()) {
+            let mut writable = permissions.clone();
+            #[allow(clippy::permissions_set_readonly_false)]
+            writable.set_readonly(false);
+            std::fs::set_permissions(path, writable)?;
+        }
+        std::fs::rename(&temp, path)?;
+        Ok(())
+    })();
+
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp);
+    }
+    result
+}
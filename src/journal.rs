@@ -0,0 +1,106 @@
+//! A record of the migrations applied in place, kept under
+//! `.elm-spa-migrate/journal` in the working directory so that they can be
+//! undone. The `index` file has one `TIMESTAMP HASH PATH` line per
+//! migration, tab separated, and the original content of each page is
+//! stored next to it in a file named after its hash.
+
+use crate::files::write_atomic;
+use anyhow::{bail, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn dir() -> PathBuf {
+    PathBuf::from(".elm-spa-migrate").join("journal")
+}
+
+fn index() -> PathBuf {
+    dir().join("index")
+}
+
+/// FNV-1a, stable across builds unlike the std hasher.
+fn hash(text: &str) -> String {
+    let hash = text.bytes().fold(0xcbf29ce484222325_u64, |h, b| {
+        (h ^ b as u64).wrapping_mul(0x100000001b3)
+    });
+    format!("{:016x}", hash)
+}
+
+pub struct Entry {
+    pub timestamp: u64,
+    pub hash: String,
+    pub path: PathBuf,
+}
+
+impl Entry {
+    fn parse(line: &str) -> Option<Self> {
+        let mut parts = line.splitn(3, '\t');
+        Some(Self {
+            timestamp: parts.next()?.parse().ok()?,
+            hash: parts.next()?.into(),
+            path: parts.next()?.into(),
+        })
+    }
+}
+
+fn entries() -> Result<Vec<Entry>> {
+    match fs::read_to_string(index()) {
+        Ok(text) => Ok(text.lines().filter_map(Entry::parse).collect()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(vec![]),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Store the current content of the page before it gets migrated.
+pub fn record(path: &Path) -> Result<()> {
+    let original = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let hash = hash(&original);
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+
+    fs::create_dir_all(dir())?;
+    fs::write(dir().join(&hash), &original)?;
+
+    let mut index = fs::read_to_string(index()).unwrap_or_default();
+    index.push_str(&format!(
+        "{}\t{}\t{}\n",
+        timestamp,
+        hash,
+        fs::canonicalize(path)?.display()
+    ));
+    write_atomic(&self::index(), index.as_bytes())?;
+    Ok(())
+}
+
+/// Restore the last recorded version of the page, or of the last migrated
+/// page if none is given, and forget about it.
+pub fn undo(path: Option<&Path>) -> Result<Entry> {
+    let mut entries = entries()?;
+    let path = path.map(fs::canonicalize).transpose()?;
+
+    let found = entries
+        .iter()
+        .rposition(|e| path.as_ref().is_none_or(|p| p == &e.path));
+    let entry = match (found, path) {
+        (Some(i), _) => entries.remove(i),
+        (None, Some(p)) => bail!("no migration of {} to undo", p.display()),
+        (None, None) => bail!("no migration to undo"),
+    };
+
+    let original = fs::read_to_string(dir().join(&entry.hash))?;
+    write_atomic(&entry.path, original.as_bytes())?;
+
+    let index: String = entries
+        .iter()
+        .map(|e| format!("{}\t{}\t{}\n", e.timestamp, e.hash, e.path.display()))
+        .collect();
+    write_atomic(&self::index(), index.as_bytes())?;
+    Ok(entry)
+}
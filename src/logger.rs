@@ -0,0 +1,190 @@
+use crate::cli::Cli;
+use crate::color::{self, ColorChoice};
+use anyhow::Result;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+
+static LOGGER: OnceLock<Logger> = OnceLock::new();
+
+/// How the warnings and other messages are printed to stderr.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum Format {
+    #[default]
+    Text,
+    /// One JSON object per line, for wrapper scripts and editor plugins.
+    Json,
+}
+
+impl Format {
+    pub fn from(string: &str) -> Option<Self> {
+        match string {
+            "text" => Some(Self::Text),
+            "json" => Some(Self::Json),
+            _ => None,
+        }
+    }
+}
+
+struct Logger {
+    color: ColorChoice,
+    format: Format,
+    level: LevelFilter,
+    file: Option<Mutex<File>>,
+    started: Instant,
+}
+
+impl Logger {
+    fn label(level: Level) -> (&'static str, &'static str) {
+        match level {
+            Level::Error => (color::RED, "error:"),
+            Level::Warn => (color::YELLOW, "warning:"),
+            Level::Info => (color::GREEN, "info:"),
+            Level::Debug => (color::CYAN, "debug:"),
+            Level::Trace => (color::BOLD, "trace:"),
+        }
+    }
+
+    /// Append the message to `--log-file`, with the time since the start.
+    fn record(&self, level: Level, message: &str) {
+        if let Some(file) = self.file.as_ref() {
+            if let Ok(mut file) = file.lock() {
+                let elapsed = self.started.elapsed().as_secs_f64();
+                let _ = writeln!(
+                    file,
+                    "[{:>9.3}s] {} {}",
+                    elapsed,
+                    Self::label(level).1,
+                    message
+                );
+            }
+        }
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let message = record.args().to_string();
+        self.record(record.level(), &message);
+
+        if record.level() <= self.level && self.format == Format::Json {
+            eprintln!("{}", json(record.level(), None, None, &message));
+        } else if record.level() <= self.level {
+            let (style, label) = Self::label(record.level());
+            eprintln!(
+                "{} {}",
+                color::paint(self.color.stderr(), style, label),
+                message
+            );
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(Ok(mut file)) = self.file.as_ref().map(Mutex::lock) {
+            let _ = file.flush();
+        }
+    }
+}
+
+/// The level to print at, from the number of `--verbose` flags, or `--quiet`.
+fn level(cli: &Cli) -> LevelFilter {
+    if cli.quiet {
+        return LevelFilter::Error;
+    }
+    match cli.verbose {
+        0 => LevelFilter::Warn,
+        1 => LevelFilter::Info,
+        2 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    }
+}
+
+fn json_string(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 2);
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// The message as a line of JSON, e.g. `{"level":"warning","file":"a.elm",
+/// "line":3,"message":"..."}`. What follows the first line of the message,
+/// like the snippet of a parse error, goes in `detail`.
+pub fn json(level: Level, file: Option<&Path>, line: Option<usize>, message: &str) -> String {
+    let label = Logger::label(level).1.trim_end_matches(':');
+    let mut fields = vec![format!("\"level\":{}", json_string(label))];
+    if let Some(file) = file {
+        let file = file.display().to_string();
+        fields.push(format!("\"file\":{}", json_string(&file)));
+    }
+    if let Some(line) = line {
+        fields.push(format!("\"line\":{}", line));
+    }
+    let (message, detail) = message.split_once('\n').unwrap_or((message, ""));
+    fields.push(format!("\"message\":{}", json_string(message)));
+    if !detail.is_empty() {
+        fields.push(format!("\"detail\":{}", json_string(detail)));
+    }
+    format!("{{{}}}", fields.join(","))
+}
+
+/// Whether the messages get printed as JSON.
+pub fn json_output() -> bool {
+    LOGGER.get().is_some_and(|l| l.format == Format::Json)
+}
+
+/// Whether messages of the level get printed to stderr.
+pub fn printing(level: Level) -> bool {
+    LOGGER.get().is_none_or(|l| level <= l.level)
+}
+
+/// Append a message printed some other way to `--log-file`.
+pub fn record(level: Level, message: &str) {
+    if let Some(logger) = LOGGER.get() {
+        logger.record(level, message);
+    }
+}
+
+pub fn init(cli: &Cli) -> Result<()> {
+    let file = cli.log_file.as_ref().map(File::create).transpose()?;
+
+    // Everything goes to the log file, which is meant to be detailed.
+    let max = if file.is_some() {
+        LevelFilter::Trace
+    } else {
+        level(cli)
+    };
+
+    let logger = LOGGER.get_or_init(|| Logger {
+        color: cli.color,
+        format: cli.warnings,
+        level: level(cli),
+        file: file.map(Mutex::new),
+        started: Instant::now(),
+    });
+    if log::set_logger(logger).is_ok() {
+        log::set_max_level(max);
+    }
+    Ok(())
+}
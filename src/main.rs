@@ -1,788 +1,1418 @@
-use anyhow::{bail, Result};
-use indoc::indoc;
-use std::collections::VecDeque;
+mod cli;
+mod color;
+mod effects;
+mod files;
+mod journal;
+mod logger;
+mod man;
+mod modules;
+mod page;
+mod pager;
+mod picker;
+mod progress;
+mod prompt;
+mod references;
+mod route;
+mod syntax;
+mod template;
+mod tui;
+
+use anyhow::{anyhow, bail, Result};
+use cli::{Cli, Command};
+use color::ColorChoice;
+use files::{discover, is_excluded, is_stdin, read_files_from, write_atomic};
+use page::{guard, Page, ParseError, Shape};
+use progress::Progress;
+use similar::TextDiff;
+use std::collections::{HashMap, HashSet};
 use std::env;
-use std::fmt;
 use std::io::prelude::*;
-use std::iter::Peekable;
-use std::path::PathBuf;
-
-fn _fmt_defs(
-    shared: bool,
-    request: bool,
-) -> (&'static str, &'static str, &'static str, &'static str) {
-    let (s_sig, s_arg) = if shared {
-        ("Shared.Model ->", "shared")
+use std::io::IsTerminal;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering as AtomicOrdering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+use template::PageType;
+
+fn read(path: &Path) -> Result<String> {
+    let bytes = if is_stdin(path) {
+        let mut bytes = vec![];
+        std::io::stdin().read_to_end(&mut bytes)?;
+        bytes
     } else {
-        ("", "")
+        std::fs::read(path)?
     };
 
-    let (r_sig, r_arg) = if request {
-        ("Request.With Params ->", "req")
+    String::from_utf8(bytes).map_err(|e| {
+        anyhow!(
+            "not valid UTF-8, the first invalid byte is at offset {}",
+            e.utf8_error().valid_up_to()
+        )
+    })
+}
+
+/// Collect the files to work on from the PATH arguments and `--files-from`.
+fn collect(cli: &Cli) -> Result<Vec<PathBuf>> {
+    let mut paths = cli.paths.clone();
+    if paths.iter().filter(|p| is_stdin(p)).count()
+        + cli.files_from.iter().filter(|p| is_stdin(p)).count()
+        > 1
+    {
+        bail!("stdin (-) can only be used once");
+    }
+
+    if let Some(list) = cli.files_from.as_ref() {
+        paths.extend(read_files_from(list)?);
+    }
+
+    let mut files = vec![];
+    for path in paths.iter() {
+        files.extend(discover(path)?);
+    }
+    let mut seen = HashSet::new();
+    files.retain(|f| seen.insert(f.clone()) && !is_excluded(f, &cli.exclude));
+    Ok(files)
+}
+
+fn load(cli: &Cli, path: &Path) -> Result<(String, Page)> {
+    log::info!("reading {}", path.display());
+    let text = read(path)?;
+    let mut page = Page::parse(&text)?;
+    if let Some(problem) = page.problems().first().filter(|_| cli.strict) {
+        return Err(problem.clone().into());
+    }
+    for problem in page.problems() {
+        color::warning_at(
+            cli.color,
+            path,
+            Some(problem.line),
+            &format!("{}, kept as is\n{}", problem.message, problem.snippet),
+        );
+    }
+    if let Some(line_ending) = cli.line_ending {
+        page = page.with_line_ending(line_ending);
+    }
+    if cli.preserve_bodies {
+        page = page.preserving_bodies();
+    }
+    if cli.remove_old {
+        page = page.removing_old();
+    }
+    if cli.protected {
+        page = page.protecting();
+    }
+    if cli.user {
+        page = page.passing_user();
+    }
+    if cli.with_query {
+        page = page.reading_query();
+    }
+    if cli.old_code_at_end {
+        page = page.moving_old_to_end();
+    }
+    if cli.old_code_to_sidecar {
+        page = page.moving_old_to_sidecar();
+    }
+    page = page.with_comment_style(cli.comment_style);
+    if let Some(marker) = cli.marker.as_deref() {
+        page = page.with_marker(marker);
+    }
+    if !cli.only_fns.is_empty() {
+        let others: Vec<&str> = page::PAGE_FUNCTIONS
+            .iter()
+            .copied()
+            .filter(|&f| !cli.only_fns.iter().any(|o| o == f))
+            .collect();
+        page = page.leaving(&others);
+    }
+    if !cli.skip_fns.is_empty() {
+        let skipped: Vec<&str> = cli.skip_fns.iter().map(String::as_str).collect();
+        page = page.leaving(&skipped);
+    }
+
+    guard(path, &page, cli.force)?;
+
+    // elm-spa expects the module name to follow the path of the page
+    if let (Some(declared), Some(expected)) = (page.module_name(), modules::from_path(path)) {
+        if declared != expected {
+            let declared = declared.to_string();
+            if cli.fix_module_name {
+                log::info!("renaming module {} to {}", declared, expected);
+                page.set_module_name(&expected);
+            } else {
+                color::warning_at(
+                    cli.color,
+                    path,
+                    None,
+                    &format!(
+                        "declares module {} but should be {}\n\
+                        Pass --fix-module-name to rename it.",
+                        declared, expected
+                    ),
+                );
+            }
+        }
+    }
+
+    Ok((text, page))
+}
+
+/// Where `--output` or `--output-dir` say the migrated page should go.
+fn destination(cli: &Cli, path: &Path) -> Result<Option<PathBuf>> {
+    let dir = match (cli.output.as_ref(), cli.output_dir.as_ref()) {
+        (Some(out), _) => return Ok(Some(out.clone())),
+        (None, Some(dir)) => dir,
+        (None, None) => return Ok(None),
+    };
+
+    if is_stdin(path) {
+        bail!("--output-dir can't be used with stdin, use --output instead");
+    }
+
+    let relative = if path.is_absolute() {
+        path.strip_prefix(env::current_dir()?).map_err(|_| {
+            anyhow!(
+                "{} is outside the current directory, can't mirror it under {}",
+                path.display(),
+                dir.display()
+            )
+        })?
     } else {
-        ("", "")
+        path
     };
 
-    (s_sig, r_sig, s_arg, r_arg)
+    let relative: PathBuf = relative
+        .components()
+        .filter(|c| matches!(c, Component::Normal(_)))
+        .collect();
+    Ok(Some(dir.join(relative)))
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
-enum PageType {
-    Static,
-    Sandbox,
-    Element,
-    Advanced,
+fn output(cli: &Cli, out: &mut dyn Write, path: &Path, original: &str, page: &Page) -> Result<()> {
+    if let Some(tool) = cli.diff_tool.as_deref() {
+        diff_tool(tool, path, original, page)?;
+    } else if cli.diff {
+        let migrated = page.to_string();
+        let name = path.display().to_string();
+        let diff = TextDiff::from_lines(original, &migrated);
+        let diff = diff
+            .unified_diff()
+            .header(&format!("a/{}", name), &format!("b/{}", name))
+            .to_string();
+        write!(out, "{}", color::diff(cli.color.stdout(), &diff))?;
+    } else if cli.dry_run && cli.annotate_provenance {
+        write!(out, "{}", color::elm(cli.color.stdout(), &page.annotated()))?;
+    } else if let Some(out) = destination(cli, path)? {
+        if let Some(dir) = out.parent().filter(|d| !d.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
+        write(&out, page)?;
+        moved(&out, page);
+    } else if cli.dry_run || is_stdin(path) {
+        let text = page.to_string();
+        write!(out, "{}", color::elm(cli.color.stdout(), &text))?;
+    } else if page.to_string() == original {
+        log::info!("{} is unchanged, leaving it as is", path.display());
+    } else {
+        backup(cli, path)?;
+        write(path, page)?;
+        moved(path, page);
+    }
+    Ok(())
 }
 
-impl PageType {
-    fn from(string: &str) -> Option<Self> {
-        match string {
-            "static" => Some(Self::Static),
-            "sandbox" => Some(Self::Sandbox),
-            "element" => Some(Self::Element),
-            "advanced" => Some(Self::Advanced),
-            _ => None,
-        }
+/// Tell where the replaced functions went, if to a sidecar file.
+fn moved(path: &Path, page: &Page) {
+    if page.old_code().is_some() {
+        println!(
+            "moved the replaced code of {} to {}",
+            path.display(),
+            sidecar(path).display()
+        );
     }
+}
 
-    fn exposing_template(self) -> &'static str {
-        match self {
-            PageType::Static => "page",
-            PageType::Sandbox => "page, Model, Msg",
-            PageType::Element => "page, Model, Msg",
-            PageType::Advanced => "page, Model, Msg",
-        }
+/// Quote the text for the shell, `sh` or `cmd` on Windows, where paths can't
+/// have `"` in them.
+fn shell_quote(text: &str) -> String {
+    if cfg!(windows) {
+        format!("\"{}\"", text)
+    } else {
+        format!("'{}'", text.replace('\'', "'\\''"))
     }
+}
 
-    fn page_template(self, shared: bool, request: bool) -> String {
-        let (_, _, s_arg, r_arg) = _fmt_defs(shared, request);
-        match self {
-            PageType::Static => format!(
-                indoc! {r###"
-                page : Shared.Model -> Request.With Params -> Page
-                page shared req =
-                    Page.static
-                        {{ view = view {s_arg} {r_arg}
-                        }}
-                "###},
-                s_arg = s_arg,
-                r_arg = r_arg
-            ),
+/// The command line run by the shell, `sh` or `cmd` on Windows.
+#[cfg(not(windows))]
+fn shell(command: &str) -> std::process::Command {
+    let mut shell = std::process::Command::new("sh");
+    shell.arg("-c").arg(command);
+    shell
+}
 
-            PageType::Sandbox => format!(
-                indoc! {r###"
-                page : Shared.Model -> Request.With Params -> Page.With Model Msg
-                page shared req =
-                    Page.sandbox
-                        {{ init = init {s_arg} {r_arg}
-                        , update = update {s_arg} {r_arg}
-                        , view = view {s_arg} {r_arg}
-                        }}
-                "###},
-                s_arg = s_arg,
-                r_arg = r_arg
-            ),
+/// The command line run by the shell, `sh` or `cmd` on Windows. It is passed
+/// as it is, `cmd` doesn't unquote its arguments the way programs do.
+#[cfg(windows)]
+fn shell(command: &str) -> std::process::Command {
+    use std::os::windows::process::CommandExt;
 
-            PageType::Element => format!(
-                indoc! {r###"
-                page : Shared.Model -> Request.With Params -> Page.With Model Msg
-                page shared req =
-                    Page.element
-                        {{ init = init {s_arg} {r_arg}
-                        , update = update {s_arg} {r_arg}
-                        , view = view {s_arg} {r_arg}
-                        , subscriptions = subscriptions {s_arg} {r_arg}
-                        }}
-                "###},
-                s_arg = s_arg,
-                r_arg = r_arg
-            ),
+    let mut shell = std::process::Command::new("cmd");
+    shell.arg("/C").raw_arg(command);
+    shell
+}
 
-            PageType::Advanced => format!(
-                indoc! {r###"
-                page : Shared.Model -> Request.With Params -> Page.With Model Msg
-                page shared req =
-                    Page.advanced
-                        {{ init = init {s_arg} {r_arg}
-                        , update = update {s_arg} {r_arg}
-                        , view = view {s_arg} {r_arg}
-                        , subscriptions = subscriptions {s_arg} {r_arg}
-                        }}
-                "###},
-                s_arg = s_arg,
-                r_arg = r_arg
-            ),
-        }
+/// Write the migrated page to a temporary file and open it next to the
+/// original with the `--diff-tool` command.
+fn diff_tool(tool: &str, path: &Path, original: &str, page: &Page) -> Result<()> {
+    let dir = env::temp_dir().join(format!("elm-spa-migrate-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("old"))?;
+    std::fs::create_dir_all(dir.join("new"))?;
+
+    let name = path
+        .file_name()
+        .filter(|_| !is_stdin(path))
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("stdin.elm"));
+
+    let old = if is_stdin(path) {
+        let old = dir.join("old").join(&name);
+        std::fs::write(&old, original)?;
+        old
+    } else {
+        path.to_path_buf()
+    };
+    let new = dir.join("new").join(&name);
+    std::fs::write(&new, page.to_string())?;
+
+    let old = shell_quote(&old.to_string_lossy());
+    let new = shell_quote(&new.to_string_lossy());
+    let command = if tool.contains("{old}") || tool.contains("{new}") {
+        tool.replace("{old}", &old).replace("{new}", &new)
+    } else {
+        format!("{} {} {}", tool, old, new)
+    };
+
+    log::info!("running {}", command);
+    let status = shell(&command).status();
+    std::fs::remove_dir_all(&dir)?;
+
+    // Diff tools exit with an error when there are differences
+    status.map_err(|e| anyhow!("failed to run --diff-tool '{}': {}", tool, e))?;
+    Ok(())
+}
+
+/// Record the file in the journal, and with `--backup` copy it to FILE.bak,
+/// before it gets overwritten.
+fn backup(cli: &Cli, path: &Path) -> Result<()> {
+    journal::record(path)?;
+    if !cli.backup || !path.exists() {
+        return Ok(());
     }
 
-    fn init_template(self, shared: bool, request: bool) -> String {
-        let (s_sig, r_sig, s_arg, r_arg) = _fmt_defs(shared, request);
+    let suffix = cli.backup_suffix.as_deref().unwrap_or(".bak");
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    std::fs::copy(path, &name)?;
+    log::info!("backed up {} to {}", path.display(), name.to_string_lossy());
+    Ok(())
+}
 
-        match self {
-            PageType::Static => "".into(),
+/// Whether a batch run writes the migrated page over the file, rather than
+/// to stdout, another file or a diff.
+fn in_place(cli: &Cli, path: &Path) -> bool {
+    !(cli.dry_run
+        || cli.diff
+        || cli.diff_tool.is_some()
+        || cli.output.is_some()
+        || cli.output_dir.is_some()
+        || is_stdin(path))
+}
 
-            PageType::Sandbox => format!(
-                indoc! {r###"
-                init : {s_sig} {r_sig} Model
-                init {s_arg} {r_arg} =
-                    {{}}
-                "###},
-                s_sig = s_sig,
-                s_arg = s_arg,
-                r_sig = r_sig,
-                r_arg = r_arg
-            ),
+/// Refuse to overwrite a read-only file in place, before going through the
+/// trouble of migrating it, unless --force.
+fn writable(cli: &Cli, path: &Path, in_place: bool) -> Result<()> {
+    let readonly = std::fs::metadata(path).is_ok_and(|m| m.permissions().readonly());
+
+    if in_place && readonly && !cli.force {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::PermissionDenied,
+            "the file is read-only\nPass --force to overwrite it anyway, it stays read-only.",
+        )
+        .into());
+    }
+    Ok(())
+}
 
-            PageType::Element => format!(
-                indoc! {r###"
-                init : {s_sig} {r_sig} (Model, Cmd Msg)
-                init {s_arg} {r_arg} =
-                    ({{}}, Cmd.none)
-                "###},
-                s_sig = s_sig,
-                s_arg = s_arg,
-                r_sig = r_sig,
-                r_arg = r_arg
-            ),
+fn write(path: &Path, page: &Page) -> Result<()> {
+    log::info!("writing {}", path.display());
+    write_atomic(path, page.to_string().as_bytes())?;
 
-            PageType::Advanced => format!(
-                indoc! {r###"
-                init : {s_sig} {r_sig} (Model, Effect Msg)
-                init {s_arg} {r_arg} =
-                    ({{}}, Effect.none)
-                "###},
-                s_sig = s_sig,
-                s_arg = s_arg,
-                r_sig = r_sig,
-                r_arg = r_arg
-            ),
-        }
+    if let Some(old) = page.old_code() {
+        let sidecar = sidecar(path);
+        log::info!("writing {}", sidecar.display());
+        write_atomic(&sidecar, old.as_bytes())?;
     }
+    Ok(())
+}
 
-    fn update_template(self: Self, shared: bool, request: bool) -> String {
-        let (s_sig, r_sig, s_arg, r_arg) = _fmt_defs(shared, request);
-
-        match self {
-            PageType::Static => "".into(),
-
-            PageType::Sandbox => format!(
-                indoc! {r###"
-                update : {s_sig} {r_sig} Msg -> Model -> Model
-                update {s_arg} {r_arg} msg model =
-                    case msg of
-                        _ ->
-                            model
-                "###},
-                s_sig = s_sig,
-                s_arg = s_arg,
-                r_sig = r_sig,
-                r_arg = r_arg
-            ),
+/// The file the replaced functions of the page go to with
+/// --old-code-to-sidecar.
+fn sidecar(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".migrated.orig");
+    PathBuf::from(name)
+}
 
-            PageType::Element => format!(
-                indoc! {r###"
-                update : {s_sig} {r_sig} Msg -> Model -> ( Model, Cmd Msg )
-                update {s_arg} {r_arg} msg model =
-                    case msg of
-                        _ ->
-                            ( model, Cmd.none )
-                "###},
-                s_sig = s_sig,
-                s_arg = s_arg,
-                r_sig = r_sig,
-                r_arg = r_arg
-            ),
+/// Whether the page is of one of the types selected with `--only`.
+fn selected(cli: &Cli, page: &Page) -> bool {
+    cli.only.is_empty()
+        || page
+            .shape()
+            .map(|s| cli.only.contains(&s.pagetype))
+            .unwrap_or(false)
+}
 
-            PageType::Advanced => format!(
-                indoc! {r###"
-                update : {s_sig} {r_sig} Msg -> Model -> ( Model, Effect Msg )
-                update {s_arg} {r_arg} msg model =
-                    case msg of
-                        _ ->
-                            ( model, Effect.none )
-                "###},
-                s_sig = s_sig,
-                s_arg = s_arg,
-                r_sig = r_sig,
-                r_arg = r_arg
-            ),
-        }
+/// Fail unless the page is currently of the type given with `--from`.
+fn source(cli: &Cli, page: &Page) -> Result<()> {
+    match (cli.from, page.shape()) {
+        (None, _) => Ok(()),
+        (Some(from), Some(shape)) if shape.pagetype == from => Ok(()),
+        (Some(from), Some(shape)) => bail!(
+            "is {} but --from expects {}",
+            shape.pagetype.name(),
+            from.name()
+        ),
+        (Some(from), None) => bail!("has no page function but --from expects {}", from.name()),
     }
+}
 
-    fn view_template(self, shared: bool, request: bool) -> String {
-        let (s_sig, r_sig, s_arg, r_arg) = _fmt_defs(shared, request);
-
-        match self {
-            PageType::Static => format!(
-                indoc! {r###"
-                view : {s_sig} {r_sig} View msg
-                view {s_arg} {r_arg} =
-                    View.placeholder "Hello World"
-                "###},
-                s_sig = s_sig,
-                s_arg = s_arg,
-                r_sig = r_sig,
-                r_arg = r_arg
-            ),
+/// Pages were changed, or would be. Not a failure as such, but scripts need
+/// to tell it apart from nothing to do, so it gets its own exit code.
+#[derive(Debug)]
+struct Changed;
 
-            PageType::Sandbox | PageType::Element | PageType::Advanced => format!(
-                indoc! {r###"
-                view : {s_sig} {r_sig} Model -> View Msg
-                view {s_arg} {r_arg} model =
-                    View.placeholder "Hello World"
-                "###},
-                s_sig = s_sig,
-                s_arg = s_arg,
-                r_sig = r_sig,
-                r_arg = r_arg
-            ),
-        }
+impl std::fmt::Display for Changed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "pages were changed")
     }
+}
 
-    fn subscriptions_template(self, shared: bool, request: bool) -> String {
-        let (s_sig, r_sig, s_arg, r_arg) = _fmt_defs(shared, request);
+impl std::error::Error for Changed {}
 
-        match self {
-            PageType::Static | PageType::Sandbox => "".into(),
+fn is_io(e: &anyhow::Error) -> bool {
+    e.chain().any(|c| c.is::<std::io::Error>())
+}
 
-            PageType::Element | PageType::Advanced => format!(
-                indoc! {r###"
-                subscriptions : {s_sig} {r_sig} Model -> Sub Msg
-                subscriptions {s_arg} {r_arg} model =
-                    Sub.none
-                "###},
-                s_sig = s_sig,
-                s_arg = s_arg,
-                r_sig = r_sig,
-                r_arg = r_arg
-            ),
-        }
+/// The error prefixed with the file it is about, and the line too if it is
+/// a parse error, e.g. `src/Pages/Foo.elm:42: could not read module name`.
+fn located(file: &Path, e: &anyhow::Error) -> String {
+    match e.downcast_ref::<ParseError>() {
+        Some(p) => format!(
+            "{}:{}: {}\n{}",
+            file.display(),
+            p.line,
+            p.message,
+            p.snippet
+        ),
+        None => format!("{}: {}", file.display(), e),
     }
 }
 
-#[derive(Debug, Clone)]
-struct Module {
-    name: String,
-    exposing: Option<String>,
+/// The error for a batch of per-file errors, an IO error if they all are.
+fn failure(errors: &[(&PathBuf, anyhow::Error)], message: String) -> anyhow::Error {
+    if errors.iter().all(|(_, e)| is_io(e)) {
+        std::io::Error::other(message).into()
+    } else {
+        anyhow!(message)
+    }
+}
+
+/// The exit code for the error, as documented in `--help`.
+fn exit_code(e: &anyhow::Error) -> i32 {
+    if e.is::<Changed>() {
+        1
+    } else if is_io(e) {
+        3
+    } else {
+        2
+    }
 }
 
-impl Module {
-    fn parse(line: String, lines: &mut Peekable<impl Iterator<Item = String>>) -> Result<Self> {
-        let name = line.split_whitespace().skip(1).next().map_or_else(
-            || bail!(format!("Failed to parse: {}", &line)),
-            |l| Ok(l.to_string()),
-        )?;
+/// What became of a file when migrating it in memory.
+enum Outcome {
+    Skipped,
+    Migrated(Option<PageType>, String, Box<Page>),
+    Failed(anyhow::Error),
+}
 
-        if !line.contains("exposing") {
-            return Ok(Self {
-                name,
-                exposing: None,
-            });
+/// The page migrated to the template, or left as it is if it already has the
+/// target shape, so that migrating again doesn't comment out its code.
+fn migrated(cli: &Cli, template: PageType, page: Page) -> Page {
+    let expected = Shape {
+        pagetype: template,
+        shared: cli.shared,
+        request: cli.request,
+        user: cli.user || cli.protected,
+        protected: cli.protected,
+    };
+    if page.shape() == Some(expected) {
+        log::info!(
+            "{} is already {}, leaving it as is",
+            page.module_name().unwrap_or("the page"),
+            expected
+        );
+        return page;
+    }
+    page.to(template, cli.shared, cli.request)
+}
+
+fn prepare(cli: &Cli, template: PageType, file: &Path) -> Outcome {
+    let started = Instant::now();
+    if let Err(e) = writable(cli, file, in_place(cli, file)) {
+        return Outcome::Failed(e);
+    }
+    match load(cli, file) {
+        Ok((_, page)) if !selected(cli, &page) => {
+            log::info!("skipping {}, not of the --only types", file.display());
+            Outcome::Skipped
+        }
+        Ok((text, page)) => {
+            if let Err(e) = source(cli, &page) {
+                return Outcome::Failed(e);
+            }
+            let from = page.shape().map(|s| s.pagetype);
+            log::info!(
+                "migrating {} from {} to {}",
+                file.display(),
+                from.map(PageType::name).unwrap_or("unknown"),
+                template.name()
+            );
+            let page = migrated(cli, template, page);
+            log::debug!("migrated {} in {:?}", file.display(), started.elapsed());
+            Outcome::Migrated(from, text, Box::new(page))
         }
+        Err(e) => Outcome::Failed(e),
+    }
+}
 
-        let mut exposing: String = line
-            .chars()
-            .skip_while(|c| c != &'(')
-            .skip(1)
-            .take_while(|c| c != &')')
-            .collect();
+fn migrate(cli: &Cli, template: PageType) -> Result<()> {
+    let files = collect(cli)?;
 
-        if !line.ends_with(')') {
-            while let Some(line) = lines.next() {
-                exposing.extend(line.chars().take_while(|c| c != &')'));
-                if line.ends_with(')') {
+    if cli.output.is_some() && files.len() != 1 {
+        bail!("--output expects a single page, got {}", files.len());
+    }
+
+    // Migrate everything in memory first so that nothing gets written
+    // unless every file could be migrated.
+    let started = Instant::now();
+    let mut progress = Progress::new(cli, files.len());
+    let mut outcomes: Vec<Option<Outcome>> = files.iter().map(|_| None).collect();
+    let next = AtomicUsize::new(0);
+    let stop = AtomicBool::new(false);
+
+    thread::scope(|scope| {
+        let (sender, receiver) = mpsc::channel();
+        for _ in 0..cli.jobs.clamp(1, files.len().max(1)) {
+            let (sender, next, stop, files) = (sender.clone(), &next, &stop, &files);
+            scope.spawn(move || loop {
+                if stop.load(AtomicOrdering::Relaxed) {
                     break;
                 }
+                let i = next.fetch_add(1, AtomicOrdering::Relaxed);
+                match files.get(i) {
+                    Some(file) => {
+                        let _ = sender.send((i, prepare(cli, template, file)));
+                    }
+                    None => break,
+                }
+            });
+        }
+        drop(sender);
+
+        // Collect in the original order whatever order they finish in.
+        for (i, outcome) in receiver {
+            match &outcome {
+                Outcome::Skipped => progress.skipped(),
+                Outcome::Migrated(_, text, page) if page.to_string() == *text => {
+                    progress.unchanged()
+                }
+                Outcome::Migrated(..) => progress.changed(),
+                Outcome::Failed(_) => {
+                    progress.failed();
+                    if cli.fail_fast {
+                        stop.store(true, AtomicOrdering::Relaxed);
+                    }
+                }
             }
+            progress.draw(&files[i]);
+            outcomes[i] = Some(outcome);
         }
+    });
+    progress.finish();
+
+    let mut pages = vec![];
+    let mut errors = vec![];
+    for (file, outcome) in files.iter().zip(outcomes) {
+        match outcome {
+            None | Some(Outcome::Skipped) => {}
+            Some(Outcome::Migrated(from, text, page)) => pages.push((file, from, text, *page)),
+            Some(Outcome::Failed(e)) => errors.push((file, e)),
+        }
+    }
+    log::info!(
+        "migrated {} of {} files in {:?}",
+        pages.len(),
+        files.len(),
+        started.elapsed()
+    );
+
+    if cli.fail_fast {
+        if let Some((file, e)) = errors.first() {
+            let message = format!(
+                "{}\nstopped at the first failure, nothing was written",
+                located(file, e)
+            );
+            return Err(failure(&errors, message));
+        }
+    } else if !errors.is_empty() && !cli.keep_going {
+        for (file, e) in errors.iter() {
+            color::error(cli.color, &located(file, e));
+        }
+        let message = format!(
+            "failed to migrate {} of {} files, nothing was written",
+            errors.len(),
+            files.len()
+        );
+        return Err(failure(&errors, message));
+    }
 
-        Ok(Self {
-            name,
-            exposing: Some(exposing),
-        })
-    }
-}
-
-#[derive(Debug, Clone)]
-struct Function {
-    lines: Vec<String>,
-}
-impl Function {
-    fn parse(
-        line: String,
-        next_lines: &mut Peekable<impl Iterator<Item = String>>,
-    ) -> Result<Self> {
-        let name = line.split_whitespace().next().map_or_else(
-            || bail!(format!("Failed to parse: {}", &line)),
-            |l| Ok(l.to_string()),
-        )?;
-
-        let mut lines = vec![line];
-        while let Some(line) = next_lines.peek() {
-            if line.trim().is_empty()
-                || line.starts_with(' ')
-                || line.starts_with('\t')
-                || line.starts_with(&format!("{} ", &name))
-            {
-                lines.push(line.into());
-                next_lines.next();
-            } else {
-                break;
+    // Long previews get paged, so collect them first.
+    let paged = (cli.dry_run || cli.diff) && !cli.no_pager && pager::wanted();
+    let mut buffer: Vec<u8> = vec![];
+    let mut stdout = std::io::stdout();
+    let out: &mut dyn Write = if paged { &mut buffer } else { &mut stdout };
+    let mut changed = false;
+
+    // Ask before rewriting each of many files, unless told not to.
+    let mut confirmed = cli.yes || cli.dry_run || cli.diff || pages.len() <= 1 || !interactive(cli);
+
+    for (file, from, text, page) in pages.iter() {
+        if !confirmed && page.to_string() != *text {
+            let name = page
+                .module_name()
+                .map(String::from)
+                .unwrap_or_else(|| file.display().to_string());
+            let question = match from {
+                Some(from) => format!(
+                    "migrate {} from {} to {}? [y/n/a/q]",
+                    name,
+                    from.name(),
+                    template.name()
+                ),
+                None => format!("migrate {} to {}? [y/n/a/q]", name, template.name()),
+            };
+
+            match prompt::answer(&question, &["y", "n", "a", "q"])?.as_str() {
+                "n" => continue,
+                "a" => confirmed = true,
+                "q" => break,
+                _ => {}
             }
         }
 
-        let func = Self { lines };
-        Ok(func)
+        if cli.dry_run && !cli.diff && pages.len() > 1 {
+            writeln!(out, "==> {} <==", file.display())?;
+        }
+        output(cli, out, file, text, page)?;
+        changed |= page.to_string() != *text;
     }
-}
 
-#[derive(Debug, Clone)]
-enum Block {
-    Module(Module),
-    Import(Module),
-    Init(Function),
-    View(Function),
-    Update(Function),
-    Subscriptions(Function),
-    Page(Function),
-    Other(String),
+    if paged {
+        pager::print(&String::from_utf8_lossy(&buffer))?;
+    }
+
+    // With --keep-going, the others were written but the failures still
+    // need to be dealt with.
+    if !errors.is_empty() {
+        eprintln!("\nFailed files:");
+        for (file, e) in errors.iter() {
+            color::error(cli.color, &located(file, e));
+        }
+        let message = format!(
+            "failed to migrate {} of {} files",
+            errors.len(),
+            files.len()
+        );
+        return Err(failure(&errors, message));
+    }
+
+    if changed {
+        return Err(Changed.into());
+    }
+    Ok(())
 }
 
-impl fmt::Display for Block {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Self::Module(m) => match &m.exposing {
-                Some(exposing) => {
-                    writeln!(f, "module {} exposing ({})", m.name, exposing)?;
-                }
-                None => {
-                    writeln!(f, "module {}", m.name)?;
-                }
-            },
+/// The comment that opts a page in to `--watch`.
+const WATCH_MARKER: &str = "-- elm-spa-migrate: watch";
+
+/// Poll the pages for changes and migrate the ones marked with
+/// [`WATCH_MARKER`] whenever they don't have the target shape yet.
+fn watch(cli: &Cli, template: PageType) -> Result<()> {
+    let expected = Shape {
+        pagetype: template,
+        shared: cli.shared,
+        request: cli.request,
+        user: cli.user || cli.protected,
+        protected: cli.protected,
+    };
+    let mut cli = with_default_paths(cli);
+    cli.diff = true;
+
+    eprintln!(
+        "watching {} for pages marked with '{}'",
+        cli.paths
+            .iter()
+            .map(|p| p.display().to_string())
+            .collect::<Vec<_>>()
+            .join(", "),
+        WATCH_MARKER
+    );
+
+    let mut seen: HashMap<PathBuf, SystemTime> = HashMap::new();
+    loop {
+        for file in collect(&cli)? {
+            let modified = match std::fs::metadata(&file).and_then(|m| m.modified()) {
+                Ok(modified) => modified,
+                Err(_) => continue,
+            };
+            if seen.insert(file.clone(), modified) == Some(modified) {
+                continue;
+            }
 
-            Self::Import(m) => match &m.exposing {
-                Some(exposing) => {
-                    writeln!(f, "import {} exposing ({})", m.name, exposing)?;
+            let (text, page) = match load(&cli, &file) {
+                Ok(loaded) => loaded,
+                Err(e) => {
+                    color::error(cli.color, &located(&file, &e));
+                    continue;
                 }
-                None => {
-                    writeln!(f, "import {}", m.name)?;
-                }
-            },
+            };
 
-            Self::Init(b)
-            | Self::Update(b)
-            | Self::View(b)
-            | Self::Subscriptions(b)
-            | Self::Page(b) => {
-                writeln!(f, "")?;
-                for line in b.lines.iter() {
-                    writeln!(f, "{}", line)?;
-                }
-                writeln!(f, "")?;
+            if !text.lines().any(|l| l.trim() == WATCH_MARKER)
+                || !selected(&cli, &page)
+                || page.shape() == Some(expected)
+            {
+                continue;
             }
 
-            Self::Other(b) => {
-                writeln!(f, "{}", b)?;
+            // Watch shows the diff, but still writes the file unless --dry-run
+            if let Err(e) = writable(&cli, &file, !cli.dry_run) {
+                color::error(cli.color, &located(&file, &e));
+                continue;
+            }
+            let page = page.to(template, cli.shared, cli.request);
+            output(&cli, &mut std::io::stdout(), &file, &text, &page)?;
+            if !cli.dry_run {
+                backup(&cli, &file)?;
+                write(&file, &page)?;
+                if let Ok(modified) = std::fs::metadata(&file).and_then(|m| m.modified()) {
+                    seen.insert(file.clone(), modified);
+                }
             }
         }
 
-        Ok(())
+        thread::sleep(Duration::from_millis(500));
     }
 }
 
-#[derive(Debug, Default)]
-struct Page {
-    blocks: Vec<Block>,
-}
-
-impl Page {
-    fn parse(text: &String) -> Result<Self> {
-        let mut page = Self::default();
-        let mut lines = text.lines().map(|l| l.trim_end().to_string()).peekable();
-
-        while let Some(line) = lines.next() {
-            if line.starts_with("module ") {
-                let module = Module::parse(line.into(), &mut lines)?;
-                page.blocks.push(Block::Module(module));
-            } else if line.starts_with("import ") {
-                let module = Module::parse(line.into(), &mut lines)?;
-                page.blocks.push(Block::Import(module));
-            } else if line.starts_with("init ") {
-                let func = Function::parse(line.into(), &mut lines)?;
-                page.blocks.push(Block::Init(func));
-            } else if line.starts_with("update ") {
-                let func = Function::parse(line.into(), &mut lines)?;
-                page.blocks.push(Block::Update(func));
-            } else if line.starts_with("view ") {
-                let func = Function::parse(line.into(), &mut lines)?;
-                page.blocks.push(Block::View(func));
-            } else if line.starts_with("subscriptions ") {
-                let func = Function::parse(line.into(), &mut lines)?;
-                page.blocks.push(Block::Subscriptions(func));
-            } else if line.starts_with("page ") {
-                let func = Function::parse(line.into(), &mut lines)?;
-                page.blocks.push(Block::Page(func));
-            } else {
-                page.blocks.push(Block::Other(line.into()));
+/// Verify that every page already has the target shape, without writing
+/// anything.
+fn check(cli: &Cli, template: PageType) -> Result<()> {
+    let files = collect(cli)?;
+    let expected = Shape {
+        pagetype: template,
+        shared: cli.shared,
+        request: cli.request,
+        user: cli.user || cli.protected,
+        protected: cli.protected,
+    };
+
+    let mut failed = 0;
+    let mut errors = vec![];
+    for file in files.iter() {
+        match load(cli, file).map(|(_, p)| (selected(cli, &p), p.shape())) {
+            Ok((false, _)) => {}
+            Ok((true, Some(shape))) if shape == expected => {}
+            Ok((true, Some(shape))) => {
+                failed += 1;
+                color::warning_at(
+                    cli.color,
+                    file,
+                    None,
+                    &format!("is {} but should be {}", shape, expected),
+                );
+            }
+            Ok((true, None)) => {
+                failed += 1;
+                color::warning_at(
+                    cli.color,
+                    file,
+                    None,
+                    &format!("has no page function but should be {}", expected),
+                );
+            }
+            Err(e) => {
+                color::error(cli.color, &located(file, &e));
+                errors.push((file, e));
             }
         }
-        Ok(page)
     }
 
-    fn to(mut self, pagetype: PageType, shared: bool, request: bool) -> Self {
-        let mut blocks = vec![];
+    if !errors.is_empty() {
+        let message = format!("failed to check {} of {} files", errors.len(), files.len());
+        return Err(failure(&errors, message));
+    }
 
-        if !self.blocks.iter().any(|b| match b {
-            Block::Import(m) => m.name == "Shared",
-            _ => false,
-        }) {
-            blocks.push(Block::Import(Module {
-                name: "Shared".into(),
-                exposing: None,
-            }))
-        };
+    if failed > 0 {
+        color::warning(
+            cli.color,
+            &format!("{} of {} files would change", failed, files.len()),
+        );
+        return Err(Changed.into());
+    }
+    Ok(())
+}
+
+/// Verify that migrating the pages a second time changes nothing, so that
+/// the tool can safely be run again.
+fn idempotence_check(cli: &Cli, template: PageType) -> Result<()> {
+    let files = collect(cli)?;
+
+    let again = |page: Page| -> Result<(String, String)> {
+        let once = migrated(cli, template, page).to_string();
+        let twice = migrated(cli, template, Page::parse(&once)?).to_string();
+        Ok((once, twice))
+    };
 
-        if !self.blocks.iter().any(|b| match b {
-            Block::Import(m) => m.name == "Request",
-            _ => false,
+    let mut failed = 0;
+    let mut errors = vec![];
+    for file in files.iter() {
+        match load(cli, file).and_then(|(_, page)| match selected(cli, &page) {
+            true => again(page).map(Some),
+            false => Ok(None),
         }) {
-            blocks.push(Block::Import(Module {
-                name: "Request".into(),
-                exposing: Some("Request".into()),
-            }))
-        };
+            Ok(None) => {}
+            Ok(Some((once, twice))) if once == twice => {}
+            Ok(Some((once, twice))) => {
+                failed += 1;
+                color::warning_at(cli.color, file, None, "migrating it again changes it");
+                let name = file.display().to_string();
+                let diff = TextDiff::from_lines(&once, &twice);
+                let diff = diff
+                    .unified_diff()
+                    .header(&format!("a/{}", name), &format!("b/{}", name))
+                    .to_string();
+                print!("{}", color::diff(cli.color.stdout(), &diff));
+            }
+            Err(e) => {
+                color::error(cli.color, &located(file, &e));
+                errors.push((file, e));
+            }
+        }
+    }
 
-        if let Some(page) = self.blocks.iter_mut().find_map(|b| match b {
-            Block::Import(m) => {
-                if m.name == "Page" {
-                    Some(m)
-                } else {
-                    None
-                }
+    if !errors.is_empty() {
+        let message = format!("failed to check {} of {} files", errors.len(), files.len());
+        return Err(failure(&errors, message));
+    }
+
+    if failed > 0 {
+        color::warning(
+            cli.color,
+            &format!(
+                "{} of {} files change when migrated again",
+                failed,
+                files.len()
+            ),
+        );
+        return Err(Changed.into());
+    }
+    Ok(())
+}
+
+/// Verify that the pages print back exactly as they were read, so that
+/// migrating them touches nothing but what changes.
+fn fmt_check(cli: &Cli) -> Result<()> {
+    let files = collect(cli)?;
+
+    let mut failed = 0;
+    let mut errors = vec![];
+    for file in files.iter() {
+        match read(file).and_then(|text| Page::parse(&text).map(|page| (text, page))) {
+            Ok((text, page)) if page.to_string() == text => {}
+            Ok((text, page)) => {
+                failed += 1;
+                color::warning_at(cli.color, file, None, "doesn't print back as it was read");
+                let printed = page.to_string();
+                let name = file.display().to_string();
+                let diff = TextDiff::from_lines(&text, &printed);
+                let diff = diff
+                    .unified_diff()
+                    .header(&format!("a/{}", name), &format!("b/{}", name))
+                    .to_string();
+                print!("{}", color::diff(cli.color.stdout(), &diff));
             }
-            _ => None,
-        }) {
-            page.exposing = Some(
-                page.exposing
-                    .as_ref()
-                    .map(|e| format!("Page, {}", e))
-                    .unwrap_or_else(|| "Page".into()),
-            )
-        } else {
-            blocks.push(Block::Import(Module {
-                name: "Page".into(),
-                exposing: Some("Page".into()),
-            }))
-        };
+            Err(e) => {
+                color::error(cli.color, &located(file, &e));
+                errors.push((file, e));
+            }
+        }
+    }
 
-        if pagetype == PageType::Advanced
-            && !self.blocks.iter().any(|b| match b {
-                Block::Import(m) => m.name == "Effect",
-                _ => false,
-            })
-        {
-            blocks.push(Block::Import(Module {
-                name: "Effect".into(),
-                exposing: Some("Effect".into()),
-            }))
-        };
+    if !errors.is_empty() {
+        let message = format!("failed to check {} of {} files", errors.len(), files.len());
+        return Err(failure(&errors, message));
+    }
 
-        for block in self.blocks.into_iter() {
-            match block {
-                Block::Module(b) => {
-                    if let Some(import) = blocks.iter_mut().find_map(|b_| match b_ {
-                        Block::Import(i) => {
-                            if i.name
-                                == format!("Gen.Params.{}", b.name.trim_start_matches("Pages."))
-                            {
-                                Some(i)
-                            } else {
-                                None
-                            }
-                        }
-                        _ => None,
-                    }) {
-                        import.exposing = Some(
-                            import
-                                .exposing
-                                .as_ref()
-                                .map(|e| format!("Params, {}", e))
-                                .unwrap_or_else(|| "Params".into()),
-                        );
-                    } else {
-                        blocks.push(Block::Import(Module {
-                            name: format!("Gen.Params.{}", b.name.trim_start_matches("Pages.")),
-                            exposing: Some("Params".into()),
-                        }))
-                    }
+    if failed > 0 {
+        color::warning(
+            cli.color,
+            &format!(
+                "{} of {} files don't print back as they were",
+                failed,
+                files.len()
+            ),
+        );
+        return Err(Changed.into());
+    }
+    Ok(())
+}
 
-                    blocks.insert(
-                        0,
-                        Block::Module(Module {
-                            name: b.name,
-                            exposing: Some(pagetype.exposing_template().into()),
-                        }),
-                    );
-                }
+/// Use the pages directory if no PATH was given.
+fn with_default_paths(cli: &Cli) -> Cli {
+    let mut cli = cli.clone();
+    if cli.paths.is_empty() {
+        cli.paths.push(PathBuf::from("src").join("Pages"));
+    }
+    cli
+}
 
-                Block::Init(b) => {
-                    blocks.push(Block::Init(Function {
-                        lines: pagetype
-                            .init_template(shared, request)
-                            .lines()
-                            .map(String::from)
-                            .collect(),
-                    }));
-                    blocks.push(Block::Other(
-                        b.lines
-                            .iter()
-                            .map(|l| format!("-- {}", l))
-                            .collect::<Vec<String>>()
-                            .join("\n"),
-                    ));
-                }
+/// A fresh page of the given template.
+fn blank(cli: &Cli, name: &str, template: PageType) -> Result<Page> {
+    let text = format!("module {}\n\nimport View exposing (View)\n\n\n", name);
+    let mut page = Page::parse(&text)?;
+    if cli.protected {
+        page = page.protecting();
+    }
+    if cli.user {
+        page = page.passing_user();
+    }
+    if cli.with_query {
+        page = page.reading_query();
+    }
+    let page = page.to(template, cli.shared, cli.request);
+    Ok(page.with_line_ending(cli.line_ending.unwrap_or_default()))
+}
 
-                Block::Update(b) => {
-                    blocks.push(Block::Update(Function {
-                        lines: pagetype
-                            .update_template(shared, request)
-                            .lines()
-                            .map(String::from)
-                            .collect(),
-                    }));
-                    blocks.push(Block::Other(
-                        b.lines
-                            .iter()
-                            .map(|l| format!("-- {}", l))
-                            .collect::<Vec<String>>()
-                            .join("\n"),
-                    ));
-                }
+/// Print a fresh page without touching any file.
+fn template(cli: &Cli, template: PageType) -> Result<()> {
+    let name = cli.module.as_deref().unwrap_or("Pages.Home_");
+    print!("{}", blank(cli, name, template)?);
+    Ok(())
+}
 
-                Block::View(b) => {
-                    blocks.push(Block::View(Function {
-                        lines: pagetype
-                            .view_template(shared, request)
-                            .lines()
-                            .map(String::from)
-                            .collect(),
-                    }));
-                    blocks.push(Block::Other(
-                        b.lines
-                            .iter()
-                            .map(|l| format!("-- {}", l))
-                            .collect::<Vec<String>>()
-                            .join("\n"),
-                    ));
-                }
+/// Create a new page from scratch.
+fn new(cli: &Cli, template: PageType) -> Result<()> {
+    let path = match cli.paths.as_slice() {
+        [path] => path,
+        _ => bail!("new expects exactly one PATH or route"),
+    };
 
-                Block::Subscriptions(b) => {
-                    blocks.push(Block::Subscriptions(Function {
-                        lines: pagetype
-                            .subscriptions_template(shared, request)
-                            .lines()
-                            .map(String::from)
-                            .collect(),
-                    }));
-                    blocks.push(Block::Other(
-                        b.lines
-                            .iter()
-                            .map(|l| format!("-- {}", l))
-                            .collect::<Vec<String>>()
-                            .join("\n"),
-                    ));
-                }
+    let path = page_path(path)?;
 
-                Block::Page(b) => {
-                    blocks.push(Block::Page(Function {
-                        lines: pagetype
-                            .page_template(shared, request)
-                            .lines()
-                            .map(String::from)
-                            .collect(),
-                    }));
-                    blocks.push(Block::Other(
-                        b.lines
-                            .iter()
-                            .map(|l| format!("-- {}", l))
-                            .collect::<Vec<String>>()
-                            .join("\n"),
-                    ));
-                }
+    if path.exists() && !cli.force {
+        bail!(
+            "{} already exists\nUse migrate to convert it, or pass --force to overwrite it.",
+            path.display()
+        );
+    }
+
+    let page = blank(cli, &page_module_name(&path)?, template)?;
+
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(&path, page.to_string())?;
+    println!("created {}", path.display());
+    Ok(())
+}
+
+fn page_path(arg: &Path) -> Result<PathBuf> {
+    match arg.to_str().filter(|p| route::is_route(p)) {
+        Some(r) => route::to_path(r),
+        None => Ok(arg.into()),
+    }
+}
+
+fn page_module_name(path: &Path) -> Result<String> {
+    modules::from_path(path).ok_or_else(|| {
+        anyhow!(
+            "could not derive a module name from {}, pages must live under a Pages directory",
+            path.display()
+        )
+    })
+}
+
+/// Move a page, renaming its module and the references to it.
+fn rename(cli: &Cli) -> Result<()> {
+    let (from, to) = match cli.paths.as_slice() {
+        [from, to] => (page_path(from)?, page_path(to)?),
+        _ => bail!("rename expects a source and a destination PATH or route"),
+    };
+
+    if !from.is_file() {
+        bail!("{} is not a file", from.display());
+    } else if to.exists() && !cli.force {
+        bail!(
+            "{} already exists\nPass --force to overwrite it.",
+            to.display()
+        );
+    }
+
+    let text = read(&from)?;
+    let old = match Page::parse(&text)?.module_name() {
+        Some(name) => name.to_string(),
+        None => page_module_name(&from)?,
+    };
+    let new = page_module_name(&to)?;
+
+    let (text, _) = references::replace(&text, &old, &new);
+    let (text, _) = references::replace(
+        &text,
+        &route::params_module(&old),
+        &route::params_module(&new),
+    );
 
-                b => blocks.push(b),
+    if let Some(dir) = to.parent().filter(|d| !d.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(&to, text)?;
+    std::fs::remove_file(&from)?;
+    println!("renamed {} to {}", from.display(), to.display());
+
+    let root = references::source_root(&to);
+    let found = references::find(&root, &to, &old)?;
+    for (file, lines) in found.iter() {
+        if cli.update_references {
+            let (text, count) = references::replace(&std::fs::read_to_string(file)?, &old, &new);
+            std::fs::write(file, text)?;
+            println!("updated {} references in {}", count, file.display());
+        } else {
+            for line in lines {
+                color::warning_at(
+                    cli.color,
+                    file,
+                    Some(*line),
+                    &format!("still refers to {}", old),
+                );
             }
         }
+    }
+
+    if !found.is_empty() && !cli.update_references {
+        println!("Pass --update-references to update them.");
+    }
+    println!("Run `elm-spa gen` to regenerate the Gen modules.");
+    Ok(())
+}
+
+/// Delete a page, and report the modules that still refer to it.
+fn delete(cli: &Cli) -> Result<()> {
+    let path = match cli.paths.as_slice() {
+        [path] => page_path(path)?,
+        _ => bail!("delete expects exactly one PATH or route"),
+    };
+
+    if !path.is_file() {
+        bail!("{} is not a file", path.display());
+    }
+
+    let module = match Page::parse(&read(&path)?)?.module_name() {
+        Some(name) => name.to_string(),
+        None => page_module_name(&path)?,
+    };
 
-        if !blocks.iter().any(|b| matches!(b, Block::Page(..))) {
-            blocks.push(Block::Other(pagetype.page_template(shared, request)));
+    std::fs::remove_file(&path)?;
+    println!("deleted {}", path.display());
+
+    let root = references::source_root(&path);
+    for (file, lines) in references::find(&root, &path, &module)? {
+        for line in lines {
+            color::warning_at(
+                cli.color,
+                &file,
+                Some(line),
+                &format!("still refers to {}", module),
+            );
         }
+    }
 
-        if pagetype != PageType::Static {
-            if !blocks.iter().any(|b| match b {
-                Block::Other(text) => text.starts_with("type alias Model ="),
-                _ => false,
-            }) {
-                blocks.push(Block::Other("\ntype alias Model = {}\n\n".into()));
-            }
+    println!("Run `elm-spa gen` to regenerate the Gen modules.");
+    Ok(())
+}
 
-            if !blocks.iter().any(|b| match b {
-                Block::Other(text) => text.starts_with("type Msg ") || text.trim() == "type Msg",
-                _ => false,
-            }) {
-                blocks.push(Block::Other("\ntype Msg = ReplaceMe\n\n".into()));
-            }
+/// Restore a page to how it was before its last migration.
+fn undo(cli: &Cli) -> Result<()> {
+    let path = match cli.paths.as_slice() {
+        [] => None,
+        [path] => Some(page_path(path)?),
+        _ => bail!("undo expects at most one PATH or route"),
+    };
 
-            if pagetype != PageType::Sandbox {
-                if !blocks.iter().any(|b| matches!(b, Block::Subscriptions(..))) {
-                    blocks.push(Block::Other(
-                        pagetype.subscriptions_template(shared, request),
-                    ));
+    let entry = journal::undo(path.as_deref())?;
+    println!("restored {}", entry.path.display());
+    Ok(())
+}
+
+/// Print a table of the pages with their detected types and wiring.
+fn list(cli: &Cli) -> Result<()> {
+    let cli = with_default_paths(cli);
+
+    let mut rows = vec![];
+    for file in collect(&cli)? {
+        let row = match read(&file).and_then(|text| Page::parse(&text)) {
+            Ok(page) => {
+                let name = page
+                    .module_name()
+                    .map(String::from)
+                    .unwrap_or_else(|| file.display().to_string());
+                match page.shape() {
+                    Some(shape) => [
+                        name,
+                        shape.pagetype.name().into(),
+                        yes_no(shape.shared).into(),
+                        yes_no(shape.request).into(),
+                    ],
+                    None => [name, "-".into(), "-".into(), "-".into()],
                 }
             }
+            Err(e) => [
+                file.display().to_string(),
+                format!("error: {}", e),
+                "".into(),
+                "".into(),
+            ],
+        };
+        rows.push(row);
+    }
+
+    let header = ["MODULE", "TYPE", "SHARED", "REQUEST"].map(String::from);
+    let width = rows
+        .iter()
+        .chain(std::iter::once(&header))
+        .map(|r| r[0].len())
+        .max()
+        .unwrap_or_default();
+
+    let bold = cli.color.stdout();
+    for (i, row) in std::iter::once(&header).chain(rows.iter()).enumerate() {
+        let line = format!(
+            "{:<width$}  {:<9} {:<7} {}",
+            row[0],
+            row[1],
+            row[2],
+            row[3],
+            width = width
+        );
+        if i == 0 {
+            println!("{}", color::paint(bold, color::BOLD, line.trim_end()));
+        } else {
+            println!("{}", line.trim_end());
+        }
+    }
+    Ok(())
+}
+
+/// Print the type of a single page and the arguments its functions take.
+fn detect(cli: &Cli) -> Result<()> {
+    let path = match cli.paths.as_slice() {
+        [path] => path,
+        [] => bail!(
+            "missing operand\nTry '{} detect --help' for more information.",
+            env!("CARGO_PKG_NAME")
+        ),
+        _ => bail!("detect expects exactly one PATH"),
+    };
+
+    let page = Page::parse(&read(path)?)?;
+    let name = page
+        .module_name()
+        .map(String::from)
+        .unwrap_or_else(|| path.display().to_string());
 
-            if !blocks.iter().any(|b| matches!(b, Block::Init(..))) {
-                blocks.push(Block::Other(pagetype.init_template(shared, request)));
+    if let Some(route) = page.module_name().and_then(route::from_module) {
+        println!("{}: {}", name, route);
+    }
+
+    match page.shape() {
+        Some(shape) => println!("{}: {}", name, shape),
+        None => println!("{}: not a page (no page function)", name),
+    }
+
+    for (function, shared, request) in page.arguments() {
+        let args = match (shared, request) {
+            (true, true) => "shared, req",
+            (true, false) => "shared",
+            (false, true) => "req",
+            (false, false) => "-",
+        };
+        println!("  {:<14} {}", function, args);
+    }
+    Ok(())
+}
+
+/// Print a project wide summary of the page types and migration leftovers.
+fn stats(cli: &Cli) -> Result<()> {
+    let cli = with_default_paths(cli);
+    let types = [
+        PageType::Static,
+        PageType::Sandbox,
+        PageType::Element,
+        PageType::Advanced,
+    ];
+
+    let files = collect(&cli)?;
+    let mut counts = [0; 4];
+    let (mut unknown, mut shared, mut request) = (0, 0, 0);
+    let mut leftovers = vec![];
+    let mut errors = vec![];
+
+    for file in files.iter() {
+        let page = match read(file).and_then(|text| Page::parse(&text)) {
+            Ok(page) => match cli.marker.as_deref() {
+                Some(marker) => page.with_marker(marker),
+                None => page,
+            },
+            Err(e) => {
+                errors.push((file, e));
+                continue;
             }
+        };
 
-            if !blocks.iter().any(|b| matches!(b, Block::Update(..))) {
-                blocks.push(Block::Other(pagetype.update_template(shared, request)));
+        match page.shape() {
+            Some(shape) => {
+                if let Some(i) = types.iter().position(|t| t == &shape.pagetype) {
+                    counts[i] += 1;
+                }
+                shared += shape.shared as usize;
+                request += shape.request as usize;
             }
+            None => unknown += 1,
         }
 
-        if !blocks.iter().any(|b| matches!(b, Block::View(..))) {
-            blocks.push(Block::Other(pagetype.view_template(shared, request)));
+        if page.has_leftovers() {
+            leftovers.push(file);
         }
+    }
 
-        Self { blocks }
+    println!("Pages: {}", files.len());
+    for (pagetype, count) in types.iter().zip(counts.iter()) {
+        println!("  {:<10} {}", pagetype.name(), count);
+    }
+    println!("  {:<10} {}", "unknown", unknown);
+    println!("Using shared: {}", shared);
+    println!("Using request: {}", request);
+    println!("With migration leftovers: {}", leftovers.len());
+    for file in leftovers {
+        println!("  {}", file.display());
     }
-}
 
-impl fmt::Display for Page {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> std::fmt::Result {
-        for b in self.blocks.iter() {
-            write!(f, "{}", b)?;
-        }
-        Ok(())
+    for (file, e) in errors.iter() {
+        color::error(cli.color, &located(file, e));
     }
+    Ok(())
 }
 
-#[derive(Debug, Clone, Default)]
-struct Cli {
-    version: bool,
-    help: bool,
-    dry_run: bool,
-    shared: bool,
-    request: bool,
-    path: Option<PathBuf>,
-    template: Option<PageType>,
+fn yes_no(value: bool) -> &'static str {
+    if value {
+        "yes"
+    } else {
+        "no"
+    }
 }
 
-impl Cli {
-    fn parse(args: env::Args) -> Result<Self> {
-        let mut args: VecDeque<String> = args.skip(1).collect();
-        let mut cli = Self::default();
+/// Ask for the template and the flags when TEMPLATE was not given.
+fn ask_template(cli: &mut Cli) -> Result<()> {
+    let options = [
+        PageType::Static,
+        PageType::Sandbox,
+        PageType::Element,
+        PageType::Advanced,
+    ]
+    .map(|t| (t.name(), t));
 
-        while let Some(arg) = args.pop_front() {
-            match arg.as_str() {
-                // Flags
-                "-h" | "--help" => {
-                    cli.help = true;
-                }
+    cli.template = Some(prompt::select("Which page template?", &options)?);
 
-                "-V" | "--version" => {
-                    cli.version = true;
-                }
+    if !cli.shared {
+        cli.shared = prompt::confirm("Pass the shared model to the page functions?", false)?;
+    }
+    if !cli.request {
+        cli.request = prompt::confirm("Pass the request object to the page functions?", false)?;
+    }
+    Ok(())
+}
 
-                "--" => {
-                    if cli.path.is_none() {
-                        cli.path = args.pop_front().map(PathBuf::from);
-                    }
-                    return Ok(cli);
-                }
+/// Replace the PATH arguments with a page picked from them.
+fn pick_page(cli: &mut Cli) -> Result<()> {
+    let mut candidates = vec![];
+    for file in collect(&with_default_paths(cli))? {
+        let name = read(&file)
+            .and_then(|text| Page::parse(&text))
+            .ok()
+            .and_then(|p| p.module_name().map(String::from))
+            .or_else(|| modules::from_path(&file))
+            .unwrap_or_else(|| file.display().to_string());
+        candidates.push((name, file));
+    }
 
-                "-s" | "--shared" => cli.shared = true,
+    cli.paths = vec![picker::pick(candidates)?];
+    cli.files_from = None;
+    Ok(())
+}
 
-                "-r" | "--request" => cli.request = true,
+/// Whether we can ask questions, i.e. stdin is a terminal that isn't used to
+/// read a page or a list of files.
+fn interactive(cli: &Cli) -> bool {
+    std::io::stdin().is_terminal()
+        && !cli
+            .paths
+            .iter()
+            .chain(cli.files_from.iter())
+            .any(|p| is_stdin(p))
+}
 
-                "--dry-run" => cli.dry_run = true,
+fn run(mut cli: Cli) -> Result<()> {
+    let interactive = interactive(&cli);
 
-                // path
-                arg => {
-                    if cli.path.is_none() {
-                        cli.path = Some(arg.into());
-                    } else if cli.template.is_none() {
-                        cli.template = PageType::from(arg);
-                    }
-                }
-            }
+    if cli.pick && !cli.help && !cli.version {
+        if !interactive {
+            bail!("--pick needs a terminal");
         }
-        Ok(cli)
+        pick_page(&mut cli)?;
     }
-}
 
-fn main() -> Result<()> {
-    let cli = Cli::parse(env::args()).unwrap_or_else(|e| {
-        eprintln!("error: {}", e);
-        std::process::exit(1);
+    if cli.command.templated()
+        && cli.command != Command::Tui
+        && cli.template.is_none()
+        && !cli.fmt_check
+        && (!cli.paths.is_empty() || cli.watch)
+        && !cli.help
+        && !cli.version
+        && interactive
+    {
+        ask_template(&mut cli)?;
+    }
+
+    let template = cli.template.filter(|_| {
+        !cli.paths.is_empty()
+            || cli.files_from.is_some()
+            || cli.watch
+            || cli.command == Command::Template
     });
 
     if cli.help {
-        let usage = format!(
-            r###"
-    {} [FLAG]... [OPTION]... [PATH] [TEMPLATE]"###,
-            env!("CARGO_PKG_NAME")
-        );
-
-        let flags = r###"
-    --                 Denotes the end of command-line flags and options
-    -s  --shared       Pass the shared model to the page functions
-    -r  --request      Pass the request object to the page functions
-        --dry-run      Print the result without overwriting file
-    -h, --help         Print help information
-    -V, --version      Print version information"###;
-
-        let args = r###"
-    <PATH>        Path to focus on, or enter if directory
-    <TEMPLATE>    Specify the target page template.
-                    Options are - static|element|sandbox|advanced"###;
-
-        let help = format!(
-            "{} {}\n{}\n{}\n\nUSAGE:{}\n\nFLAGS:{}\n\nARGS:{}",
-            env!("CARGO_PKG_NAME"),
-            env!("CARGO_PKG_VERSION"),
-            env!("CARGO_PKG_AUTHORS"),
-            env!("CARGO_PKG_DESCRIPTION"),
-            usage,
-            flags,
-            args,
-        );
-        let help = help.trim();
-
-        println!("{}", help);
+        println!("{}", cli.command.help());
         Ok(())
     } else if cli.version {
-        println!("xplr {}", env!("CARGO_PKG_VERSION"));
+        println!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
         Ok(())
-    } else if let Some((path, template)) =
-        cli.path.as_ref().and_then(|p| cli.template.map(|t| (p, t)))
+    } else if cli.output.is_some() && cli.output_dir.is_some() {
+        bail!("--output can't be used with --output-dir");
+    } else if (cli.output.is_some() || cli.output_dir.is_some()) && (cli.dry_run || cli.diff) {
+        bail!("--output and --output-dir can't be used with --dry-run or --diff");
+    } else if cli.annotate_provenance && !cli.dry_run {
+        bail!("--annotate-provenance can only be used with --dry-run");
+    } else if let (true, Some(template)) = (cli.watch, template) {
+        watch(&cli, template)
+    } else if let (Command::Migrate | Command::Diff, Some(template)) = (cli.command, template) {
+        migrate(&cli, template)
+    } else if cli.command == Command::Check && cli.fmt_check {
+        fmt_check(&cli)
+    } else if let (Command::Check, Some(template), true) =
+        (cli.command, template, cli.check_idempotent)
     {
-        let text = std::fs::read_to_string(&path)?;
-
-        let page = Page::parse(&text)?.to(template, cli.shared, cli.request);
+        idempotence_check(&cli, template)
+    } else if let (Command::Check, Some(template)) = (cli.command, template) {
+        check(&cli, template)
+    } else if let (Command::New, Some(template)) = (cli.command, template) {
+        new(&cli, template)
+    } else if let (Command::Template, Some(t)) = (cli.command, template) {
+        self::template(&cli, t)
+    } else if cli.command == Command::Man {
+        print!("{}", man::render());
+        Ok(())
+    } else if cli.command == Command::Tui {
+        let cli = with_default_paths(&cli);
+        tui::run(&cli, collect(&cli)?)
+    } else if cli.command == Command::Rename {
+        rename(&cli)
+    } else if cli.command == Command::Undo {
+        undo(&cli)
+    } else if cli.command == Command::Delete {
+        delete(&cli)
+    } else if cli.command == Command::List {
+        list(&cli)
+    } else if cli.command == Command::Detect {
+        detect(&cli)
+    } else if cli.command == Command::Stats {
+        stats(&cli)
+    } else {
+        bail!(
+            "missing operand\nTry '{} {} --help' for more information.",
+            env!("CARGO_PKG_NAME"),
+            cli.command.name()
+        );
+    }
+}
 
-        if cli.dry_run {
-            println!("{}", page);
-        } else {
-            let mut file = std::fs::OpenOptions::new()
-                .write(true)
-                .truncate(true)
-                .open(path)?;
+fn main() {
+    let cli = Cli::parse(env::args()).unwrap_or_else(|e| {
+        color::error(ColorChoice::Auto, &e.to_string());
+        std::process::exit(2);
+    });
 
-            writeln!(file, "{}", page)?;
+    let choice = cli.color;
+    if let Err(e) = logger::init(&cli).and_then(|_| run(cli)) {
+        if !e.is::<Changed>() {
+            color::error(choice, &e.to_string());
         }
-        Ok(())
-    } else {
-        bail!("missing operand\nTry 'rm --help' for more information.");
+        std::process::exit(exit_code(&e));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_only_pages_are_refused_unless_forced() {
+        let path = env::temp_dir().join(format!("elm-spa-migrate-{}.elm", std::process::id()));
+        std::fs::write(&path, "module Pages.About exposing (page)\n").unwrap();
+        let mut permissions = std::fs::metadata(&path).unwrap().permissions();
+        permissions.set_readonly(true);
+        std::fs::set_permissions(&path, permissions).unwrap();
+
+        let mut cli = Cli::default();
+        let batch = writable(&cli, &path, in_place(&cli, &path));
+        // Watch shows a diff, and writes the file all the same
+        cli.diff = true;
+        let watched = writable(&cli, &path, !cli.dry_run);
+        cli.force = true;
+        let forced = writable(&cli, &path, !cli.dry_run);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(exit_code(&batch.unwrap_err()), 3);
+        assert_eq!(exit_code(&watched.unwrap_err()), 3);
+        assert!(forced.is_ok());
     }
 }
@@ -0,0 +1,104 @@
+use crate::cli::{Command, EXIT_STATUS};
+use crate::template::PageType;
+
+const TYPES: [PageType; 4] = [
+    PageType::Static,
+    PageType::Sandbox,
+    PageType::Element,
+    PageType::Advanced,
+];
+
+/// Escape the text so that roff prints it as is.
+fn escape(text: &str) -> String {
+    let text = text.replace('\\', "\\e").replace('-', "\\-");
+    if text.starts_with(['.', '\'']) {
+        format!("\\&{}", text)
+    } else {
+        text
+    }
+}
+
+/// Join the lines of a help entry that were wrapped for the terminal.
+fn unwrap(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn items(out: &mut String, items: Vec<(&str, &str)>) {
+    for (key, value) in items {
+        out.push_str(&format!(
+            ".TP\n.B {}\n{}\n",
+            escape(&unwrap(key)),
+            escape(&unwrap(value))
+        ));
+    }
+}
+
+/// The manual page in roff format, generated from the CLI definition.
+pub fn render() -> String {
+    let name = escape(env!("CARGO_PKG_NAME"));
+    let mut out = format!(
+        ".TH {} 1 \"\" \"{} {}\"\n",
+        name.to_uppercase(),
+        name,
+        env!("CARGO_PKG_VERSION")
+    );
+
+    out.push_str(&format!(
+        ".SH NAME\n{} \\- {}\n",
+        name,
+        escape(env!("CARGO_PKG_DESCRIPTION"))
+    ));
+
+    out.push_str(".SH SYNOPSIS\n");
+    for cmd in Command::ALL {
+        out.push_str(&format!(".B {}\n{}\n.br\n", name, escape(cmd.usage())));
+    }
+
+    out.push_str(".SH COMMANDS\n");
+    for cmd in Command::ALL {
+        out.push_str(&format!(
+            ".SS {}\n{}.\n.PP\n.B {}\n{}\n",
+            cmd.name(),
+            escape(cmd.about()),
+            name,
+            escape(cmd.usage())
+        ));
+        items(&mut out, cmd.flags());
+        items(&mut out, cmd.options());
+        items(&mut out, cmd.args());
+    }
+
+    out.push_str(".SH TEMPLATES\n");
+    for pagetype in TYPES {
+        out.push_str(&format!(
+            ".TP\n.B {}\n{}.\n.RS\n.PP\nExample:\n.PP\n.nf\n.RS\n{} src/Pages/Home_.elm {}\n{} \\-\\-shared \\-\\-request src/Pages/Home_.elm {}\n.RE\n.fi\n.RE\n",
+            pagetype.name(),
+            escape(pagetype.description()),
+            name,
+            pagetype.name(),
+            name,
+            pagetype.name()
+        ));
+    }
+
+    out.push_str(".SH EXIT STATUS\n");
+    items(&mut out, EXIT_STATUS.to_vec());
+
+    out.push_str(&format!(
+        ".SH EXAMPLES\n\
+        Preview the migration of a page without writing it:\n\
+        .PP\n.nf\n.RS\n{name} \\-\\-dry\\-run src/Pages/Home_.elm element\n.RE\n.fi\n\
+        .PP\n\
+        Migrate every page in a directory:\n\
+        .PP\n.nf\n.RS\n{name} src/Pages element\n.RE\n.fi\n\
+        .PP\n\
+        Create a new page for a route:\n\
+        .PP\n.nf\n.RS\n{name} new /users/:id advanced \\-\\-shared\n.RE\n.fi\n\
+        .PP\n\
+        Install this manual page:\n\
+        .PP\n.nf\n.RS\n{name} man > ~/.local/share/man/man1/{name}.1\n.RE\n.fi\n",
+        name = name
+    ));
+
+    out
+}
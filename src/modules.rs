@@ -0,0 +1,147 @@
+//! Mapping from page files to their module names, e.g.
+//! `src/Pages/Users/Id_.elm` is `Pages.Users.Id_`.
+//!
+//! Paths may be written the Windows way, with `\` separators and a drive
+//! prefix like `C:`, whatever the platform. On case-insensitive file systems
+//! `src/pages/users/id_.elm` is the same file, so the module name is spelled
+//! the way the files are on disk, which is what the Elm compiler checks.
+
+use std::ffi::OsString;
+use std::path::{Component, Path, PathBuf};
+
+/// The path with each of its parts spelled the way it is on disk, as far as
+/// it exists.
+fn on_disk(path: &Path) -> PathBuf {
+    let mut resolved = PathBuf::new();
+    for component in path.components() {
+        let name = component.as_os_str();
+        let spelled: Option<OsString> = match component {
+            Component::Normal(_) => {
+                let dir = if resolved.as_os_str().is_empty() {
+                    Path::new(".")
+                } else {
+                    resolved.as_path()
+                };
+                std::fs::read_dir(dir).ok().and_then(|entries| {
+                    let names: Vec<OsString> = entries
+                        .filter_map(|e| e.ok().map(|e| e.file_name()))
+                        .collect();
+                    // An exact match wins on case-sensitive file systems
+                    if names.iter().any(|n| n == name) {
+                        None
+                    } else {
+                        names.into_iter().find(|n| n.eq_ignore_ascii_case(name))
+                    }
+                })
+            }
+            _ => None,
+        };
+        resolved.push(spelled.as_deref().unwrap_or(name));
+    }
+    resolved
+}
+
+/// Whether the file system of the path ignores case, as far as the nearest
+/// existing part of it can tell, or else as the platform usually does.
+fn case_insensitive(path: &Path) -> bool {
+    let existing = path.ancestors().find_map(|p| {
+        let name = p.file_name()?.to_str()?;
+        let swapped = if name == name.to_lowercase() {
+            name.to_uppercase()
+        } else {
+            name.to_lowercase()
+        };
+        (swapped != name && p.exists()).then(|| p.with_file_name(swapped))
+    });
+    match existing {
+        Some(swapped) => swapped.exists(),
+        None => cfg!(any(windows, target_os = "macos")),
+    }
+}
+
+/// The directories and the file name of the path, split on both `/` and `\`,
+/// without any drive prefix.
+fn parts(path: &Path) -> Vec<String> {
+    let path = path.to_string_lossy();
+    let mut parts: Vec<String> = path
+        .split(['/', '\\'])
+        .filter(|p| !p.is_empty() && *p != ".")
+        .map(String::from)
+        .collect();
+
+    let drive = parts
+        .first()
+        .is_some_and(|p| p.len() == 2 && p.ends_with(':'));
+    if drive {
+        parts.remove(0);
+    }
+    parts
+}
+
+/// Derive the module name of a page from its path, e.g.
+/// `src/Pages/Users/Id_.elm` or `C:\app\src\Pages\Users\Id_.elm` is
+/// `Pages.Users.Id_`.
+pub fn from_path(path: &Path) -> Option<String> {
+    let mut parts = parts(&on_disk(path));
+    let file = parts.pop()?;
+    let stem = match file.len().checked_sub(4) {
+        Some(i) if file.is_char_boundary(i) && file[i..].eq_ignore_ascii_case(".elm") => &file[..i],
+        _ => file.as_str(),
+    };
+    parts.push(stem.to_string());
+
+    // A page can itself live in a `Pages` directory, e.g. `src/Pages/Pages/Foo.elm`
+    let ignore_case = case_insensitive(path);
+    let is_pages = |p: &String| p == "Pages" || ignore_case && p.eq_ignore_ascii_case("pages");
+    let pages = parts
+        .windows(2)
+        .position(|w| w[0].eq_ignore_ascii_case("src") && is_pages(&w[1]))
+        .map(|i| i + 1)
+        .or_else(|| parts.iter().rposition(is_pages))?;
+    if pages + 1 == parts.len() {
+        return None;
+    }
+
+    let mut name = String::from("Pages");
+    for part in &parts[pages + 1..] {
+        name.push('.');
+        name.push_str(part);
+    }
+    Some(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn module(path: &str) -> Option<String> {
+        from_path(Path::new(path))
+    }
+
+    #[test]
+    fn from_path_reads_windows_paths() {
+        assert_eq!(
+            module(r"src\Pages\Users\Id_.elm").as_deref(),
+            Some("Pages.Users.Id_")
+        );
+        assert_eq!(
+            module(r"C:\app\src\Pages\Users\Id_.elm").as_deref(),
+            Some("Pages.Users.Id_")
+        );
+        assert_eq!(
+            module(r"c:\Projects\App\SRC\Pages\Admin\Users.ELM").as_deref(),
+            Some("Pages.Admin.Users")
+        );
+        assert_eq!(
+            module(r"C:/app\src/Pages\Home_.elm").as_deref(),
+            Some("Pages.Home_")
+        );
+    }
+
+    #[test]
+    fn from_path_ignores_the_case_of_pages_only_where_the_file_system_does() {
+        let path = "src/pages/Foo.elm";
+        let expected = case_insensitive(Path::new(path)).then(|| "Pages.Foo".to_string());
+        assert_eq!(module(path), expected);
+    }
+}
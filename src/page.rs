@@ -0,0 +1,2611 @@
+use crate::effects;
+use crate::modules;
+use crate::route;
+use crate::syntax::{self, Kind};
+use crate::template::{self, PageType};
+use anyhow::{bail, Result};
+use std::fmt;
+use std::iter::Peekable;
+use std::path::Path;
+
+/// Length of the comment at the start of the text, if it starts with one.
+/// A block comment left open runs up to the end of the line.
+fn comment_len(text: &str) -> Option<usize> {
+    let line = text.find('\n').unwrap_or(text.len());
+    if text.starts_with("--") {
+        return Some(line);
+    } else if !text.starts_with("{-") {
+        return None;
+    }
+
+    let mut depth = 0;
+    let mut i = 0;
+    while i < line {
+        if text[i..].starts_with("{-") {
+            depth += 1;
+            i += 2;
+        } else if text[i..].starts_with("-}") {
+            depth -= 1;
+            i += 2;
+            if depth == 0 {
+                return Some(i);
+            }
+        } else {
+            i += text[i..].chars().next().map_or(1, char::len_utf8);
+        }
+    }
+    Some(line)
+}
+
+/// A name in an `exposing (...)` clause, along with the comments next to it.
+#[derive(Debug, Clone, Default)]
+struct Item {
+    name: String,
+    comments: Vec<String>,
+}
+
+impl Item {
+    fn is(&self, name: &str) -> bool {
+        self.name == name || self.name.strip_suffix("(..)") == Some(name)
+    }
+}
+
+/// The names listed in an `exposing (...)` clause.
+#[derive(Debug, Clone, Default)]
+pub struct Exposing {
+    items: Vec<Item>,
+}
+
+impl Exposing {
+    fn parse(text: &str) -> Self {
+        let mut items = vec![];
+        let mut item = Item::default();
+        let mut depth = 0;
+        let mut i = 0;
+        while i < text.len() {
+            let rest = &text[i..];
+            if let Some(len) = comment_len(rest) {
+                item.comments.push(rest[..len].trim_end().to_string());
+                i += len;
+                continue;
+            }
+
+            let c = rest.chars().next().unwrap_or_default();
+            i += c.len_utf8();
+            match c {
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                ',' if depth == 0 => {
+                    items.push(std::mem::take(&mut item));
+                    continue;
+                }
+                _ => {}
+            }
+            if !c.is_whitespace() {
+                item.name.push(c);
+            }
+        }
+        items.push(item);
+
+        // Comments after the last name go along with it
+        if let Some(last) = items.pop() {
+            match items.last_mut() {
+                Some(previous) if last.name.is_empty() => previous.comments.extend(last.comments),
+                _ => items.push(last),
+            }
+        }
+        items.retain(|i| !i.name.is_empty() || !i.comments.is_empty());
+        Self { items }
+    }
+
+    fn names(&self) -> impl DoubleEndedIterator<Item = &str> {
+        self.items.iter().map(|i| i.name.as_str())
+    }
+
+    /// Whether it is `exposing (..)`, which exposes everything defined.
+    fn everything(&self) -> bool {
+        self.names().any(|n| n == "..")
+    }
+
+    /// Whether the name is exposed, with or without its constructors.
+    fn contains(&self, name: &str) -> bool {
+        self.everything() || self.items.iter().any(|i| i.is(name))
+    }
+
+    /// Stop exposing the name, returning whether it was.
+    fn remove(&mut self, name: &str) -> bool {
+        if self.everything() {
+            return false;
+        }
+        let len = self.items.len();
+        self.items.retain(|i| !i.is(name));
+        self.items.len() != len
+    }
+
+    /// Expose the name first, unless it already is, returning whether it
+    /// wasn't.
+    fn add(&mut self, name: &str) -> bool {
+        if self.contains(name) {
+            return false;
+        }
+        self.items.insert(
+            0,
+            Item {
+                name: name.to_string(),
+                comments: vec![],
+            },
+        );
+        true
+    }
+}
+
+/// Printed as it follows `exposing`, on the same line unless a `--` comment
+/// forces one name per line, the way elm-format does.
+impl fmt::Display for Exposing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> std::fmt::Result {
+        let items: Vec<String> = self
+            .items
+            .iter()
+            .map(|i| {
+                std::iter::once(i.name.as_str())
+                    .chain(i.comments.iter().map(String::as_str))
+                    .filter(|s| !s.is_empty())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect();
+
+        if self
+            .items
+            .iter()
+            .any(|i| i.comments.iter().any(|c| c.starts_with("--")))
+        {
+            write!(f, "\n    ( {}\n    )", items.join("\n    , "))
+        } else {
+            write!(f, " ({})", items.join(", "))
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Module {
+    /// `port` or `effect` in front of a module header.
+    qualifier: Option<String>,
+    name: String,
+    /// The `as` name of an import.
+    alias: Option<String>,
+    /// The `where { ... }` clause of an effect module.
+    manager: Option<String>,
+    exposing: Option<Exposing>,
+    /// The text it was parsed from, printed as is unless it changes.
+    source: Option<String>,
+}
+
+impl Module {
+    fn parse(line: String, lines: &mut Peekable<impl Iterator<Item = String>>) -> Result<Self> {
+        let mut words = line.split_whitespace();
+        let qualifier = match words.next() {
+            Some(q @ "port") | Some(q @ "effect") => {
+                words.next();
+                Some(q.to_string())
+            }
+            _ => None,
+        };
+        let name = match words.next() {
+            Some(name) if name.starts_with(char::is_uppercase) => name.to_string(),
+            _ if line.starts_with("import") => bail!("could not read the imported module name"),
+            _ => bail!("could not read module name"),
+        };
+        let alias = match words.next() {
+            Some("as") => words.next().map(String::from),
+            _ => None,
+        };
+
+        let mut source = vec![line.clone()];
+        let manager = line
+            .split_once(" where ")
+            .map(|(_, clause)| clause.split(" exposing").next().unwrap_or(clause))
+            .map(|clause| clause.trim().to_string());
+
+        if !line.contains("exposing") {
+            return Ok(Self {
+                qualifier,
+                name,
+                alias,
+                manager,
+                exposing: None,
+                source: Some(line),
+            });
+        }
+
+        // Everything up to the matching paren, e.g. with `Msg(..)` or comments
+        // inside, which may span the following lines
+        let mut exposing = String::new();
+        let mut depth = 0;
+        let mut closed = false;
+        let mut rest = line.split_once("exposing").map(|(_, r)| r.to_string());
+        'list: while let Some(text) = rest {
+            let mut i = 0;
+            while i < text.len() {
+                let rest = &text[i..];
+                if let Some(len) = comment_len(rest) {
+                    exposing.push_str(&rest[..len]);
+                    i += len;
+                    continue;
+                }
+
+                let c = rest.chars().next().unwrap_or_default();
+                i += c.len_utf8();
+                match c {
+                    '(' => depth += 1,
+                    ')' if depth == 1 => {
+                        closed = true;
+                        break 'list;
+                    }
+                    ')' => depth -= 1,
+                    _ => {}
+                }
+                if depth > 1 || (depth == 1 && c != '(') {
+                    exposing.push(c);
+                }
+            }
+            exposing.push('\n');
+            rest = lines.next();
+            source.extend(rest.clone());
+        }
+
+        if !closed {
+            bail!("the exposing list is never closed");
+        }
+
+        Ok(Self {
+            qualifier,
+            name,
+            alias,
+            manager,
+            exposing: Some(Exposing::parse(&exposing)),
+            source: Some(source.join("\n")),
+        })
+    }
+
+    fn expose(&mut self, name: &str) {
+        if self
+            .exposing
+            .get_or_insert_with(Exposing::default)
+            .add(name)
+        {
+            self.source = None;
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Function {
+    /// The `{-| ... -}` doc comment right above the function.
+    doc: Vec<String>,
+    lines: Vec<String>,
+}
+impl Function {
+    fn name(&self) -> &str {
+        let line = self.lines.first().map(String::as_str).unwrap_or_default();
+        let end = line
+            .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .unwrap_or(line.len());
+        &line[..end]
+    }
+
+    /// The line where the function is defined, and its argument names. The
+    /// definition may fit on that line, e.g. `update = always identity`.
+    fn definition(&self) -> Option<(usize, Vec<&str>)> {
+        let name = self.name();
+        self.lines.iter().enumerate().find_map(|(i, l)| {
+            let rest = l.strip_prefix(name)?;
+            if !rest.starts_with([' ', '=']) || rest.trim_start().starts_with(':') {
+                return None;
+            }
+            let (args, _) = rest.split_once('=')?;
+            Some((i, args.split_whitespace().collect()))
+        })
+    }
+
+    /// Whether the body of the function refers to the name, e.g. `shared` in
+    /// `shared.user`.
+    fn uses(&self, name: &str) -> bool {
+        self.body().is_some_and(|body| {
+            syntax::tokens(&body)
+                .iter()
+                .any(|t| t.text.split('.').next() == Some(name))
+        })
+    }
+
+    /// What the function is defined as, after the `=`.
+    fn body(&self) -> Option<String> {
+        let (line, _) = self.definition()?;
+        let text = self.lines[line..].join("\n");
+        let (_, body) = text.split_once('=')?;
+        Some(body.trim().to_string())
+    }
+
+    /// The function taking one more argument, named `_` if the name is
+    /// already used in its body.
+    fn with_argument(mut self, name: &str) -> Self {
+        let Some((line, _)) = self.definition() else {
+            return self;
+        };
+        let used = self.lines[line..]
+            .iter()
+            .any(|l| words(l).any(|(_, w)| w == name));
+        let name = if used { "_" } else { name };
+
+        let definition = &mut self.lines[line];
+        if let Some(i) = definition.find('=') {
+            let args = definition[..i].trim_end().len();
+            definition.insert_str(args, &format!(" {}", name));
+        }
+        self
+    }
+
+    /// Whether the function takes the signed in user first, read from its
+    /// type annotation, or from its argument names if it has none.
+    fn takes_user(&self) -> bool {
+        let (line, args) = self.definition().unwrap_or((self.lines.len(), vec![]));
+        if line == 0 {
+            args.first() == Some(&"user")
+        } else {
+            self.lines[..line]
+                .join(" ")
+                .split_once(':')
+                .is_some_and(|(_, annotation)| annotation.trim_start().starts_with("Auth.User"))
+        }
+    }
+
+    /// Whether the function takes the shared model and the request, read
+    /// from its type annotation, or from its argument names if it has none.
+    fn arguments(&self) -> (bool, bool) {
+        let (line, args) = self.definition().unwrap_or((self.lines.len(), vec![]));
+        let annotation: Vec<&str> = self.lines[..line].iter().map(String::as_str).collect();
+
+        if annotation.is_empty() {
+            (args.contains(&"shared"), args.contains(&"req"))
+        } else {
+            let annotation = annotation.join(" ");
+            (
+                annotation.contains("Shared.Model"),
+                annotation.contains("Request.With") || annotation.contains("Request.Request"),
+            )
+        }
+    }
+}
+
+/// The text with its comments blanked out, e.g. those between the
+/// constructors of a custom type.
+fn uncommented(text: &str) -> String {
+    let mut code = String::with_capacity(text.len());
+    let mut i = 0;
+    while i < text.len() {
+        match comment_len(&text[i..]) {
+            Some(len) => {
+                code.push(' ');
+                i += len;
+            }
+            None => {
+                let c = text[i..].chars().next().unwrap_or_default();
+                code.push(c);
+                i += c.len_utf8();
+            }
+        }
+    }
+    code
+}
+
+/// The type name at the start of the word, e.g. `Model` in `Model=`.
+fn type_name(word: &str) -> Option<&str> {
+    let end = word
+        .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .unwrap_or(word.len());
+    Some(&word[..end]).filter(|name| name.starts_with(char::is_uppercase))
+}
+
+/// A type alias declaration, e.g. `type alias Model = { count : Int }`, kept
+/// as is.
+#[derive(Debug, Clone)]
+pub struct TypeAlias {
+    name: String,
+    /// The type it names, with the comments blanked out.
+    aliased: String,
+    text: String,
+}
+
+impl TypeAlias {
+    /// Read the declaration, however it's laid out, unless it isn't one of a
+    /// type alias.
+    fn parse(text: &str) -> Option<Self> {
+        let code = uncommented(text);
+        let mut words = code.split_whitespace();
+        if words.next() != Some("type") || words.next() != Some("alias") {
+            return None;
+        }
+        let name = words.next().and_then(type_name)?;
+        let (_, aliased) = code.split_once('=')?;
+
+        Some(Self {
+            name: name.to_string(),
+            aliased: aliased.trim().to_string(),
+            text: text.to_string(),
+        })
+    }
+
+    /// An empty value of the type it names, e.g. `{ count = 0 }` for
+    /// `{ count : Int }`, unless some part of it has none.
+    fn empty(&self) -> Option<String> {
+        empty(&self.aliased)
+    }
+}
+
+/// An empty value of the type, e.g. `0` for `Int` or `[]` for `List a`,
+/// unless it has none or it can't tell.
+fn empty(ty: &str) -> Option<String> {
+    let ty = ty.trim();
+    let tokens = syntax::tokens(ty);
+    if tokens.iter().any(|t| t.depth == 0 && t.text == "->") {
+        return None;
+    }
+
+    if let Some(fields) = ty.strip_prefix('{').and_then(|t| t.strip_suffix('}')) {
+        if fields.trim().is_empty() {
+            return Some("{}".into());
+        }
+        // Extensible records don't name all their fields
+        if syntax::split(fields, '|').len() > 1 {
+            return None;
+        }
+        let fields = syntax::split(fields, ',')
+            .into_iter()
+            .map(|field| {
+                let (name, ty) = fields[field].split_once(':')?;
+                Some(format!("{} = {}", name.trim(), empty(ty)?))
+            })
+            .collect::<Option<Vec<String>>>()?;
+        return Some(format!("{{ {} }}", fields.join(", ")));
+    }
+
+    if syntax::parens(ty).first() == Some(&(0..ty.len())) {
+        let inner = &ty[1..ty.len() - 1];
+        if inner.trim().is_empty() {
+            return Some("()".into());
+        }
+        let parts = syntax::split(inner, ',');
+        if parts.len() == 1 {
+            return empty(inner);
+        }
+        let values = parts
+            .into_iter()
+            .map(|part| empty(&inner[part]))
+            .collect::<Option<Vec<String>>>()?;
+        return Some(format!("( {} )", values.join(", ")));
+    }
+
+    let value = match tokens.first()?.text {
+        "Int" | "Float" => "0",
+        "String" | "String.String" => "\"\"",
+        "Bool" => "False",
+        "List" => "[]",
+        "Maybe" | "Maybe.Maybe" => "Nothing",
+        "Dict" | "Dict.Dict" => "Dict.empty",
+        "Set" | "Set.Set" => "Set.empty",
+        "Array" | "Array.Array" => "Array.empty",
+        _ => return None,
+    };
+    Some(value.into())
+}
+
+/// A custom type declaration, e.g. `type Msg = Increment | SetName String`,
+/// kept as is.
+#[derive(Debug, Clone)]
+pub struct CustomType {
+    name: String,
+    /// The constructors, and how many arguments each takes.
+    constructors: Vec<(String, usize)>,
+    text: String,
+}
+
+impl CustomType {
+    /// Read the declaration, unless it isn't one of a custom type, e.g. a
+    /// `type alias`.
+    fn parse(text: &str) -> Option<Self> {
+        let code = uncommented(text);
+        let mut words = code.split_whitespace();
+        if words.next() != Some("type") {
+            return None;
+        }
+        let name = words.next().filter(|n| *n != "alias").and_then(type_name)?;
+
+        let constructors = match code.split_once('=') {
+            Some((_, rest)) => syntax::split(rest, '|')
+                .into_iter()
+                .filter_map(|variant| {
+                    let variant = &rest[variant];
+                    let mut args = syntax::split(variant, ' ')
+                        .into_iter()
+                        .map(|arg| variant[arg].trim())
+                        .filter(|a| !a.is_empty());
+                    let name = args.next()?.to_string();
+                    Some((name, args.count()))
+                })
+                .collect(),
+            None => vec![],
+        };
+
+        Some(Self {
+            name: name.to_string(),
+            constructors,
+            text: text.to_string(),
+        })
+    }
+}
+
+/// The functions making up a page, which get replaced by the templates.
+pub const PAGE_FUNCTIONS: [&str; 5] = ["init", "update", "view", "subscriptions", "page"];
+
+#[derive(Debug, Clone)]
+pub enum Block {
+    Module(Module),
+    Import(Module),
+    Init(Function),
+    View(Function),
+    Update(Function),
+    Subscriptions(Function),
+    Page(Function),
+    /// A `port name : Type` declaration, kept as is.
+    Port(Vec<String>),
+    Type(CustomType),
+    Alias(TypeAlias),
+    Other(String),
+}
+
+impl Block {
+    fn function(&self) -> Option<&Function> {
+        match self {
+            Self::Init(f)
+            | Self::Update(f)
+            | Self::View(f)
+            | Self::Subscriptions(f)
+            | Self::Page(f) => Some(f),
+            _ => None,
+        }
+    }
+
+    fn function_mut(&mut self) -> Option<&mut Function> {
+        match self {
+            Self::Init(f)
+            | Self::Update(f)
+            | Self::View(f)
+            | Self::Subscriptions(f)
+            | Self::Page(f) => Some(f),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Block {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Module(Module {
+                source: Some(source),
+                ..
+            })
+            | Self::Import(Module {
+                source: Some(source),
+                ..
+            }) => {
+                writeln!(f, "{}", source)?;
+            }
+
+            Self::Module(m) => {
+                if let Some(qualifier) = &m.qualifier {
+                    write!(f, "{} ", qualifier)?;
+                }
+                write!(f, "module {}", m.name)?;
+                if let Some(manager) = &m.manager {
+                    write!(f, " where {}", manager)?;
+                }
+                match &m.exposing {
+                    Some(exposing) => writeln!(f, " exposing{}", exposing)?,
+                    None => writeln!(f)?,
+                }
+            }
+
+            Self::Import(m) => {
+                write!(f, "import {}", m.name)?;
+                if let Some(alias) = &m.alias {
+                    write!(f, " as {}", alias)?;
+                }
+                match &m.exposing {
+                    Some(exposing) => writeln!(f, " exposing{}", exposing)?,
+                    None => writeln!(f)?,
+                }
+            }
+
+            Self::Init(b)
+            | Self::Update(b)
+            | Self::View(b)
+            | Self::Subscriptions(b)
+            | Self::Page(b) => {
+                for line in b.doc.iter().chain(b.lines.iter()) {
+                    writeln!(f, "{}", line)?;
+                }
+            }
+
+            Self::Port(lines) => {
+                for line in lines.iter() {
+                    writeln!(f, "{}", line)?;
+                }
+            }
+
+            Self::Type(CustomType { text, .. })
+            | Self::Alias(TypeAlias { text, .. })
+            | Self::Other(text) => {
+                writeln!(f, "{}", text)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The page type and wiring of a page, as read from its `page` function.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Shape {
+    pub pagetype: PageType,
+    pub shared: bool,
+    pub request: bool,
+    /// Whether it passes the signed in user to the page functions.
+    pub user: bool,
+    /// Whether it is `Page.protected`, for signed in users only.
+    pub protected: bool,
+}
+
+impl fmt::Display for Shape {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.protected {
+            write!(f, "protected ")?;
+        }
+        write!(f, "{}", self.pagetype.name())?;
+        let passed: Vec<&str> = [
+            (self.user, "user"),
+            (self.shared, "shared"),
+            (self.request, "request"),
+        ]
+        .iter()
+        .filter(|(passes, _)| *passes)
+        .map(|(_, name)| *name)
+        .collect();
+        match passed.split_last() {
+            Some((last, [])) => write!(f, " with {}", last),
+            Some((last, rest)) => write!(f, " with {} and {}", rest.join(", "), last),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Where a block of the migrated page came from.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Provenance {
+    Generated,
+    Preserved,
+    Original,
+}
+
+impl fmt::Display for Provenance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Generated => write!(f, "generated"),
+            Self::Preserved => write!(f, "preserved"),
+            Self::Original => write!(f, "original (commented)"),
+        }
+    }
+}
+
+/// Something in a page that could not be parsed, and where.
+#[derive(Debug, Clone)]
+pub struct ParseError {
+    pub line: usize,
+    pub message: String,
+    /// The lines around it, numbered.
+    pub snippet: String,
+}
+
+impl ParseError {
+    fn new(text: &str, line: usize, message: String) -> Self {
+        let snippet = text
+            .lines()
+            .enumerate()
+            .map(|(i, l)| (i + 1, l))
+            .skip(line.saturating_sub(3))
+            .take(5)
+            .map(|(n, l)| {
+                let marker = if n == line { '>' } else { ' ' };
+                format!("{} {:>4} | {}", marker, n, l)
+            })
+            .collect::<Vec<String>>()
+            .join("\n");
+
+        Self {
+            line,
+            message,
+            snippet,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}\n{}", self.line, self.message, self.snippet)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    Crlf,
+}
+
+impl LineEnding {
+    pub fn from(string: &str) -> Option<Self> {
+        match string {
+            "lf" => Some(Self::Lf),
+            "crlf" => Some(Self::Crlf),
+            "native" if cfg!(windows) => Some(Self::Crlf),
+            "native" => Some(Self::Lf),
+            _ => None,
+        }
+    }
+
+    /// The line ending used by most lines of the text.
+    fn detect(text: &str) -> Self {
+        let crlf = text.matches("\r\n").count();
+        let lf = text.matches('\n').count() - crlf;
+        if crlf > lf {
+            Self::Crlf
+        } else {
+            Self::Lf
+        }
+    }
+}
+
+/// How the replaced functions are commented out.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum CommentStyle {
+    /// `-- ` at the start of each line.
+    #[default]
+    Line,
+    /// A single `{- -}` around the whole function.
+    Block,
+}
+
+impl CommentStyle {
+    pub fn from(string: &str) -> Option<Self> {
+        match string {
+            "line" => Some(Self::Line),
+            "block" => Some(Self::Block),
+            _ => None,
+        }
+    }
+}
+
+/// Line number of the first merge conflict marker left in the text, if any.
+fn conflict(text: &str) -> Option<usize> {
+    let is_marker = |line: &str, marker: &str| {
+        line.strip_prefix(marker)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with(char::is_whitespace))
+    };
+
+    // Markers in a multi-line string or a comment are just text
+    let literal = syntax::literal_lines(text);
+    let lines: Vec<(usize, &str)> = text
+        .lines()
+        .enumerate()
+        .map(|(i, l)| (i + 1, l))
+        .filter(|(n, _)| !literal.contains(n))
+        .collect();
+
+    let start = lines.iter().position(|(_, l)| is_marker(l, "<<<<<<<"))?;
+    lines[start..]
+        .iter()
+        .any(|(_, l)| is_marker(l, "======="))
+        .then_some(lines[start].0)
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Page {
+    blocks: Vec<Block>,
+    provenance: Vec<Provenance>,
+    /// Whether the text it was parsed from lacks a final newline.
+    unterminated: bool,
+    line_ending: LineEnding,
+    /// Whether the text it was parsed from starts with a byte order mark.
+    bom: bool,
+    /// What could not be parsed, and was kept as is.
+    problems: Vec<ParseError>,
+    /// Whether migrating keeps the bodies of the page functions.
+    preserve_bodies: bool,
+    /// The page functions migrating leaves as they are.
+    untouched: Vec<String>,
+    /// The page functions that kept their bodies, with only their annotation
+    /// and arguments rewritten.
+    rewritten: Vec<String>,
+    /// Whether migrating deletes the replaced functions.
+    remove_old: bool,
+    /// Whether migrating makes it a protected page, passing the user along.
+    protected: bool,
+    /// Whether migrating passes the user to the page functions.
+    user: bool,
+    /// Whether the init template reads the query of the request.
+    with_query: bool,
+    /// Whether migrating moves the replaced functions to the end.
+    old_code_at_end: bool,
+    /// Whether migrating moves the replaced functions out of the page.
+    old_code_to_sidecar: bool,
+    /// The replaced functions moved out of the page.
+    old_code: Vec<String>,
+    comment_style: CommentStyle,
+    /// What the replaced functions are commented out with instead of `--`.
+    marker: Option<String>,
+}
+
+impl Page {
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut page = Self::default();
+
+        // Some Windows editors start files with a byte order mark
+        let text = match text.strip_prefix('\u{feff}') {
+            Some(text) => {
+                page.bom = true;
+                text
+            }
+            None => text,
+        };
+
+        if let Some(line) = conflict(text) {
+            return Err(ParseError::new(
+                text,
+                line,
+                "unresolved merge conflict, resolve it before migrating".into(),
+            )
+            .into());
+        }
+
+        let decls = syntax::parse(text);
+
+        // e.g. after a bad merge, which would leave two of each
+        let mut defined: Vec<(&str, usize)> = vec![];
+        for decl in decls.iter().filter(|d| d.kind == Kind::Value) {
+            let name = decl.name.unwrap_or_default();
+            if let Some((_, first)) = defined.iter().find(|(n, _)| *n == name) {
+                return Err(ParseError::new(
+                    text,
+                    decl.line,
+                    format!(
+                        "{} is defined twice, at lines {} and {}",
+                        name, first, decl.line
+                    ),
+                )
+                .into());
+            }
+            defined.push((name, decl.line));
+        }
+
+        let mut decls = decls.into_iter().peekable();
+        let mut doc = vec![];
+
+        let lines = |text: &str| -> Vec<String> {
+            text.split_terminator('\n')
+                .map(|l| l.strip_suffix('\r').unwrap_or(l).to_string())
+                .collect()
+        };
+        // What isn't migrated is kept as the slice of the source it was
+        // read from, blank lines and trailing spaces included
+        let raw = |text: &str| -> String { lines(text).join("\n") };
+        page.unterminated = !text.is_empty() && !text.ends_with('\n');
+        page.line_ending = LineEnding::detect(text);
+
+        while let Some(decl) = decls.next() {
+            log::trace!(
+                "line {}, bytes {:?}: {:?} {}",
+                decl.line,
+                decl.span,
+                decl.kind,
+                decl.name.unwrap_or_default()
+            );
+
+            match (decl.kind, decl.name) {
+                (Kind::Module, _) | (Kind::Import, _) => {
+                    let mut rest = lines(decl.text).into_iter().peekable();
+                    match rest.next().map(|line| Module::parse(line, &mut rest)) {
+                        Some(Ok(module)) => {
+                            if decl.kind == Kind::Module {
+                                page.push(Provenance::Preserved, Block::Module(module));
+                            } else {
+                                page.push(Provenance::Preserved, Block::Import(module));
+                            }
+                            let rest: Vec<String> = rest.collect();
+                            if !rest.is_empty() {
+                                page.push(Provenance::Preserved, Block::Other(rest.join("\n")));
+                            }
+                        }
+                        Some(Err(e)) => {
+                            page.problems
+                                .push(ParseError::new(text, decl.line, e.to_string()));
+                            page.push(Provenance::Preserved, Block::Other(raw(decl.text)));
+                        }
+                        None => {}
+                    }
+                }
+
+                (Kind::Annotation, Some(name)) | (Kind::Value, Some(name))
+                    if PAGE_FUNCTIONS.contains(&name) =>
+                {
+                    let mut func = Function {
+                        doc: std::mem::take(&mut doc),
+                        lines: lines(decl.text),
+                    };
+
+                    // The definition that goes with the annotation, possibly
+                    // with comments in between
+                    if decl.kind == Kind::Annotation
+                        && decls
+                            .clone()
+                            .find(|d| d.kind != Kind::Comment)
+                            .is_some_and(|d| d.kind == Kind::Value && d.name == Some(name))
+                    {
+                        for d in decls.by_ref() {
+                            func.lines.extend(lines(d.text));
+                            if d.kind == Kind::Value {
+                                break;
+                            }
+                        }
+                    }
+
+                    let block = match name {
+                        "init" => Block::Init(func),
+                        "update" => Block::Update(func),
+                        "view" => Block::View(func),
+                        "subscriptions" => Block::Subscriptions(func),
+                        _ => Block::Page(func),
+                    };
+                    page.push(Provenance::Preserved, block);
+                }
+
+                // A doc comment goes along with the function right below it
+                (Kind::Comment, _)
+                    if decl.text.starts_with("{-|")
+                        && !decl.text.ends_with("\n\n")
+                        && decls.peek().is_some_and(|d| {
+                            matches!(d.kind, Kind::Annotation | Kind::Value)
+                                && d.name.is_some_and(|n| PAGE_FUNCTIONS.contains(&n))
+                        }) =>
+                {
+                    doc = lines(decl.text);
+                }
+
+                // A call at column 0 to the function right above, which is
+                // out of the offside rule, still belongs to its body
+                (Kind::Other, _)
+                    if page
+                        .blocks
+                        .last_mut()
+                        .and_then(Block::function_mut)
+                        .is_some_and(|f| decl.text.split_whitespace().next() == Some(f.name())) =>
+                {
+                    if let Some(f) = page.blocks.last_mut().and_then(Block::function_mut) {
+                        f.lines.extend(lines(decl.text));
+                    }
+                }
+
+                (Kind::Port, _) => {
+                    page.push(Provenance::Preserved, Block::Port(lines(decl.text)));
+                }
+
+                (Kind::Type, _) => {
+                    let text = raw(decl.text);
+                    let block = match (CustomType::parse(&text), TypeAlias::parse(&text)) {
+                        (Some(custom), _) => Block::Type(custom),
+                        (None, Some(alias)) => Block::Alias(alias),
+                        (None, None) => Block::Other(text),
+                    };
+                    page.push(Provenance::Preserved, block);
+                }
+
+                (Kind::Other, _) => {
+                    page.problems.push(ParseError::new(
+                        text,
+                        decl.line,
+                        "unrecognized declaration".into(),
+                    ));
+                    page.push(Provenance::Preserved, Block::Other(raw(decl.text)));
+                }
+
+                _ => {
+                    page.push(Provenance::Preserved, Block::Other(raw(decl.text)));
+                }
+            }
+        }
+
+        for block in page.blocks.iter() {
+            match block {
+                Block::Module(m) => log::debug!("detected module {}", m.name),
+                Block::Import(m) => log::debug!("detected import {}", m.name),
+                Block::Init(f)
+                | Block::Update(f)
+                | Block::View(f)
+                | Block::Subscriptions(f)
+                | Block::Page(f) => log::debug!("detected {} function", f.name()),
+                Block::Port(lines) => log::debug!(
+                    "detected port {}",
+                    lines
+                        .first()
+                        .and_then(|l| l.split_whitespace().nth(1))
+                        .unwrap_or_default()
+                ),
+                Block::Type(t) => log::debug!(
+                    "detected type {} with {} constructors",
+                    t.name,
+                    t.constructors.len()
+                ),
+                Block::Alias(a) => log::debug!("detected type alias {}", a.name),
+                Block::Other(_) => {}
+            }
+        }
+        Ok(page)
+    }
+
+    pub fn to(mut self, pagetype: PageType, shared: bool, request: bool) -> Self {
+        let mut page = Self {
+            unterminated: self.unterminated,
+            bom: self.bom,
+            line_ending: self.line_ending,
+            preserve_bodies: self.preserve_bodies,
+            untouched: self.untouched.clone(),
+            remove_old: self.remove_old,
+            protected: self.protected,
+            user: self.user,
+            with_query: self.with_query,
+            old_code_at_end: self.old_code_at_end,
+            old_code_to_sidecar: self.old_code_to_sidecar,
+            comment_style: self.comment_style,
+            marker: self.marker.clone(),
+            ..Self::default()
+        };
+
+        // Protected pages pass the signed in user to the page functions
+        let (user, protected) = (self.user || self.protected, self.protected);
+        if user && !protected {
+            log::warn!(
+                "the page function has no user to pass to the page functions \
+                 unless the page is protected, pass --protected or bind one in it"
+            );
+        }
+
+        // The templates refer to these modules by name, unless imported `as`
+        let aliases: Vec<(String, String)> = self
+            .blocks
+            .iter()
+            .filter_map(|b| match b {
+                Block::Import(Module {
+                    name,
+                    alias: Some(alias),
+                    ..
+                }) if matches!(
+                    name.as_str(),
+                    "Shared" | "Request" | "Page" | "Effect" | "Auth"
+                ) =>
+                {
+                    Some((format!("{}.", name), format!("{}.", alias)))
+                }
+                _ => None,
+            })
+            .collect();
+
+        // Kept bodies of sandbox pages send nothing, element ones commands and
+        // advanced ones effects
+        let from = self.shape().map(|s| s.pagetype);
+        let effects: Option<fn(&str, &str) -> String> = match (from, pagetype) {
+            (Some(PageType::Sandbox), PageType::Element) => Some(effects::with_cmds),
+            (Some(PageType::Sandbox), PageType::Advanced) => Some(effects::with_effects),
+            (Some(PageType::Element), PageType::Advanced) => Some(effects::to_effects),
+            (Some(PageType::Advanced), PageType::Element) => Some(effects::to_cmds),
+            (Some(PageType::Element | PageType::Advanced), PageType::Sandbox) => {
+                Some(effects::without_cmds)
+            }
+            _ => None,
+        };
+
+        // Going down to a simpler page type keeps what still fits in it
+        let downgraded = from.is_some_and(|from| from > pagetype);
+        let preserve = self.preserve_bodies || downgraded;
+
+        // What the page functions took, to pass what they gain where they
+        // call each other
+        let callees: Vec<(String, bool, bool, bool)> = self
+            .blocks
+            .iter()
+            .filter_map(|b| match b {
+                Block::Init(f) | Block::Update(f) | Block::View(f) | Block::Subscriptions(f) => {
+                    let (shared, request) = f.arguments();
+                    Some((f.name().to_string(), f.takes_user(), shared, request))
+                }
+                _ => None,
+            })
+            .collect();
+        // Without a page function, a page with only a view is static too
+        let promoted = pagetype != PageType::Static
+            && match self.shape() {
+                Some(shape) => shape.pagetype == PageType::Static,
+                None => !self
+                    .blocks
+                    .iter()
+                    .any(|b| matches!(b, Block::Init(_) | Block::Update(_))),
+            };
+
+        // Kept bodies still using the shared model or the request keep
+        // taking them, as if -s or -r was given
+        let (mut shared, mut request) = (shared, request);
+        let kept = self.blocks.iter().filter_map(|b| match b {
+            Block::View(f) if preserve || promoted => Some(f),
+            Block::Init(f) | Block::Update(f) if preserve && pagetype != PageType::Static => {
+                Some(f)
+            }
+            Block::Subscriptions(f) if preserve && pagetype >= PageType::Element => Some(f),
+            _ => None,
+        });
+        for f in kept {
+            let (takes_shared, takes_request) = f.arguments();
+            let args = f.definition().map(|(_, args)| args).unwrap_or_default();
+            let used = |i: usize| args.get(i).filter(|&&a| a != "_" && f.uses(a));
+            let first = f.takes_user() as usize;
+            if let Some(arg) = used(first).filter(|_| takes_shared && !shared) {
+                log::warn!(
+                    "{} still uses {}, so it keeps taking the shared model as with -s",
+                    f.name(),
+                    arg
+                );
+                shared = true;
+            }
+            if let Some(arg) =
+                used(first + takes_shared as usize).filter(|_| takes_request && !request)
+            {
+                log::warn!(
+                    "{} still uses {}, so it keeps taking the request as with -r",
+                    f.name(),
+                    arg
+                );
+                request = true;
+            }
+        }
+
+        // The update template handles each message of the page
+        let messages: Vec<(String, usize)> = self
+            .blocks
+            .iter()
+            .find_map(|b| match b {
+                Block::Type(t) if t.name == "Msg" => Some(t.constructors.clone()),
+                _ => None,
+            })
+            .unwrap_or_default();
+
+        // The init and view templates show how to read the params of the route
+        let params = self.module_name().map(route::params).unwrap_or_default();
+        let routed = |template: String| template::with_params(template, &params);
+        // And the init one how to read its query, given a request to read from
+        let with_query = self.with_query && request && pagetype != PageType::Static;
+        let queried = |template: String| {
+            if with_query {
+                template::with_query(template)
+            } else {
+                template
+            }
+        };
+
+        // The init template starts with an empty model of the page
+        let model = self
+            .blocks
+            .iter()
+            .find_map(|b| match b {
+                Block::Alias(a) if a.name == "Model" => a.empty(),
+                _ => None,
+            })
+            .unwrap_or_else(|| "{}".into());
+
+        if !self.blocks.iter().any(|b| match b {
+            Block::Import(m) => m.name == "Shared",
+            _ => false,
+        }) {
+            page.push(
+                Provenance::Generated,
+                Block::Import(Module {
+                    qualifier: None,
+                    name: "Shared".into(),
+                    alias: None,
+                    manager: None,
+                    exposing: None,
+                    source: None,
+                }),
+            )
+        };
+
+        if user
+            && !self.blocks.iter().any(|b| match b {
+                Block::Import(m) => m.name == "Auth",
+                _ => false,
+            })
+        {
+            page.push(
+                Provenance::Generated,
+                Block::Import(Module {
+                    qualifier: None,
+                    name: "Auth".into(),
+                    alias: None,
+                    manager: None,
+                    exposing: None,
+                    source: None,
+                }),
+            )
+        };
+
+        // Reading the query takes a Dict
+        if with_query
+            && !self.blocks.iter().any(|b| match b {
+                Block::Import(m) => m.name == "Dict",
+                _ => false,
+            })
+        {
+            page.push(
+                Provenance::Generated,
+                Block::Import(Module {
+                    qualifier: None,
+                    name: "Dict".into(),
+                    alias: None,
+                    manager: None,
+                    exposing: None,
+                    source: None,
+                }),
+            )
+        };
+
+        if !self.blocks.iter().any(|b| match b {
+            Block::Import(m) => m.name == "Request",
+            _ => false,
+        }) {
+            page.push(
+                Provenance::Generated,
+                Block::Import(Module {
+                    qualifier: None,
+                    name: "Request".into(),
+                    alias: None,
+                    manager: None,
+                    exposing: Some(Exposing::parse("Request")),
+                    source: None,
+                }),
+            )
+        };
+
+        if let Some(import) = self.blocks.iter_mut().find_map(|b| match b {
+            Block::Import(m) => {
+                if m.name == "Page" {
+                    Some(m)
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        }) {
+            import.expose("Page");
+        } else {
+            page.push(
+                Provenance::Generated,
+                Block::Import(Module {
+                    qualifier: None,
+                    name: "Page".into(),
+                    alias: None,
+                    manager: None,
+                    exposing: Some(Exposing::parse("Page")),
+                    source: None,
+                }),
+            )
+        };
+
+        if pagetype == PageType::Advanced
+            && !self.blocks.iter().any(|b| match b {
+                Block::Import(m) => m.name == "Effect",
+                _ => false,
+            })
+        {
+            page.push(
+                Provenance::Generated,
+                Block::Import(Module {
+                    qualifier: None,
+                    name: "Effect".into(),
+                    alias: None,
+                    manager: None,
+                    exposing: Some(Exposing::parse("Effect")),
+                    source: None,
+                }),
+            )
+        };
+
+        if let Some(params) = self.module_name().map(route::params_module) {
+            if let Some(import) = self.blocks.iter_mut().find_map(|b| match b {
+                Block::Import(m) if m.name == params => Some(m),
+                _ => None,
+            }) {
+                import.expose("Params");
+            } else {
+                page.push(
+                    Provenance::Generated,
+                    Block::Import(Module {
+                        qualifier: None,
+                        name: params,
+                        alias: None,
+                        manager: None,
+                        exposing: Some(Exposing::parse("Params")),
+                        source: None,
+                    }),
+                )
+            }
+        }
+
+        // What comes before the module line, e.g. a license header, stays there
+        let header = self
+            .blocks
+            .iter()
+            .position(|b| matches!(b, Block::Module(_)))
+            .filter(|&i| {
+                self.blocks[..i]
+                    .iter()
+                    .any(|b| !b.to_string().trim().is_empty())
+            })
+            .unwrap_or_default();
+
+        let untouched = self.untouched.clone();
+        let touched = |name: &str| !untouched.iter().any(|u| u == name);
+
+        for block in self.blocks.into_iter() {
+            if block.function().is_some_and(|f| !touched(f.name())) {
+                page.push(Provenance::Preserved, block);
+                continue;
+            }
+
+            match block {
+                Block::Module(b) => {
+                    let start = page.blocks.len() - header;
+                    let blocks: Vec<Block> = page.blocks.drain(start..).collect();
+                    let provenance: Vec<Provenance> = page.provenance.drain(start..).collect();
+                    for (i, (p, b)) in provenance.into_iter().zip(blocks).enumerate() {
+                        page.insert(i, p, b);
+                    }
+
+                    // Keep exposing whatever else the module did
+                    let mut exposing = b.exposing.unwrap_or_default();
+                    let mut changed = false;
+                    let required = Exposing::parse(pagetype.exposing_template());
+                    for name in required.names().rev() {
+                        changed |= exposing.add(name);
+                    }
+
+                    // Types the template doesn't define would fail to compile
+                    if pagetype == PageType::Static {
+                        for name in ["Model", "Msg"] {
+                            if exposing.remove(name) {
+                                changed = true;
+                                log::warn!(
+                                    "{} no longer exposes {}, static pages don't define it",
+                                    b.name,
+                                    name
+                                );
+                            }
+                        }
+                    }
+
+                    page.insert(
+                        header,
+                        Provenance::Generated,
+                        Block::Module(Module {
+                            qualifier: b.qualifier,
+                            name: b.name,
+                            alias: None,
+                            manager: b.manager,
+                            exposing: Some(exposing),
+                            source: b.source.filter(|_| !changed),
+                        }),
+                    );
+                }
+
+                Block::Init(b) => page.replace(
+                    Block::Init,
+                    &routed(queried(
+                        pagetype.init_template(shared, request, user, &model),
+                    )),
+                    b,
+                    (shared, request, user),
+                    preserve,
+                    effects,
+                ),
+
+                Block::Update(b) => page.replace(
+                    Block::Update,
+                    &pagetype.update_template(shared, request, user, &messages),
+                    b,
+                    (shared, request, user),
+                    preserve,
+                    effects,
+                ),
+
+                // The view of a static page becomes that of the stateful one
+                Block::View(b) if promoted => page.replace(
+                    Block::View,
+                    &pagetype.view_template(shared, request, user),
+                    b.with_argument("model"),
+                    (shared, request, user),
+                    true,
+                    None,
+                ),
+
+                Block::View(b) => page.replace(
+                    Block::View,
+                    &routed(pagetype.view_template(shared, request, user)),
+                    b,
+                    (shared, request, user),
+                    preserve,
+                    None,
+                ),
+
+                Block::Subscriptions(b) => {
+                    if pagetype < PageType::Element && b.body().as_deref() != Some("Sub.none") {
+                        log::warn!(
+                            "{} pages can't subscribe, dropped the subscriptions",
+                            pagetype.name()
+                        );
+                    }
+                    page.replace(
+                        Block::Subscriptions,
+                        &pagetype.subscriptions_template(shared, request, user),
+                        b,
+                        (shared, request, user),
+                        preserve,
+                        None,
+                    )
+                }
+
+                Block::Page(b) => {
+                    page.push(
+                        Provenance::Generated,
+                        Block::Page(Function {
+                            lines: named_like(
+                                &pagetype.page_template(shared, request, user, protected),
+                                &b,
+                            ),
+                            doc: b.doc.clone(),
+                        }),
+                    );
+                    page.supersede(&b);
+                }
+
+                b => page.push(Provenance::Preserved, b),
+            }
+        }
+
+        if touched("page") && !page.blocks.iter().any(|b| matches!(b, Block::Page(..))) {
+            page.push(
+                Provenance::Generated,
+                Block::Other(pagetype.page_template(shared, request, user, protected)),
+            );
+        }
+
+        if pagetype != PageType::Static {
+            if !page.has_type("Model") {
+                page.push(
+                    Provenance::Generated,
+                    Block::Other("\ntype alias Model = {}\n\n".into()),
+                );
+            }
+
+            if !page.has_type("Msg") {
+                page.push(
+                    Provenance::Generated,
+                    Block::Other("\ntype Msg = ReplaceMe\n\n".into()),
+                );
+            }
+
+            if pagetype != PageType::Sandbox
+                && touched("subscriptions")
+                && !page
+                    .blocks
+                    .iter()
+                    .any(|b| matches!(b, Block::Subscriptions(..)))
+            {
+                page.push(
+                    Provenance::Generated,
+                    Block::Other(pagetype.subscriptions_template(shared, request, user)),
+                );
+            }
+
+            if touched("init") && !page.blocks.iter().any(|b| matches!(b, Block::Init(..))) {
+                page.push(
+                    Provenance::Generated,
+                    Block::Other(routed(queried(
+                        pagetype.init_template(shared, request, user, &model),
+                    ))),
+                );
+            }
+
+            if touched("update") && !page.blocks.iter().any(|b| matches!(b, Block::Update(..))) {
+                page.push(
+                    Provenance::Generated,
+                    Block::Other(pagetype.update_template(shared, request, user, &messages)),
+                );
+            }
+        }
+
+        if touched("view") && !page.blocks.iter().any(|b| matches!(b, Block::View(..))) {
+            page.push(
+                Provenance::Generated,
+                Block::Other(routed(pagetype.view_template(shared, request, user))),
+            );
+        }
+
+        // The module documentation has to stay above the imports, and
+        // document what the module exposes
+        if let Some(m) = page
+            .blocks
+            .iter()
+            .position(|b| matches!(b, Block::Module(_)))
+        {
+            let after: Vec<usize> = (m + 1..page.blocks.len())
+                .filter(|&i| page.provenance[i] == Provenance::Preserved)
+                .collect();
+            let doc = after
+                .iter()
+                .copied()
+                .find(|&i| !page.blocks[i].to_string().trim().is_empty())
+                .filter(|&i| matches!(&page.blocks[i], Block::Other(t) if t.starts_with("{-|")));
+
+            if let Some(doc) = doc {
+                let exposing = match &page.blocks[m] {
+                    Block::Module(Module {
+                        exposing: Some(exposing),
+                        ..
+                    }) => Some(exposing.clone()),
+                    _ => None,
+                };
+                if let (Some(exposing), Block::Other(text)) = (exposing, &mut page.blocks[doc]) {
+                    if let Some(updated) = documented(text, &exposing) {
+                        log::info!("updated the @docs of the module documentation");
+                        *text = updated;
+                    }
+                }
+
+                let first = after[0];
+                let blocks: Vec<Block> = page.blocks.drain(first..=doc).collect();
+                let provenance: Vec<Provenance> = page.provenance.drain(first..=doc).collect();
+                for (i, (p, b)) in provenance.into_iter().zip(blocks).enumerate() {
+                    page.insert(m + 1 + i, p, b);
+                }
+            }
+        }
+
+        // The functions that kept their bodies take what the template passes too
+        let rewritten = page.rewritten.clone();
+        let templated = |provenance: &Provenance, f: &Function| match provenance {
+            Provenance::Generated => true,
+            Provenance::Preserved => rewritten.iter().any(|r| r == f.name()),
+            Provenance::Original => false,
+        };
+
+        for (provenance, block) in page.provenance.iter().zip(page.blocks.iter_mut()) {
+            match block {
+                Block::Init(f) | Block::Update(f) | Block::View(f) | Block::Subscriptions(f)
+                    if templated(provenance, f) =>
+                {
+                    threaded(f, &callees, (shared, request, user))
+                }
+                _ => {}
+            }
+        }
+
+        // Effects only come with advanced pages
+        if from == Some(PageType::Advanced) && pagetype != PageType::Advanced {
+            page.unimport("Effect");
+        }
+
+        // And the user with the page functions taking it
+        if !user && callees.iter().any(|(_, had_user, _, _)| *had_user) {
+            page.unimport("Auth");
+        }
+
+        // Only port modules may declare ports
+        if page.blocks.iter().any(|b| matches!(b, Block::Port(_))) {
+            if let Some(m) = page.blocks.iter_mut().find_map(|b| match b {
+                Block::Module(m) if m.qualifier.is_none() => Some(m),
+                _ => None,
+            }) {
+                log::warn!("{} declares ports, making it a port module", m.name);
+                m.qualifier = Some("port".into());
+                m.source = None;
+            }
+        }
+
+        if !aliases.is_empty() {
+            for (provenance, block) in page.provenance.iter().zip(page.blocks.iter_mut()) {
+                match (provenance, block) {
+                    (_, Block::Init(f))
+                    | (_, Block::Update(f))
+                    | (_, Block::View(f))
+                    | (_, Block::Subscriptions(f))
+                    | (_, Block::Page(f))
+                        if templated(provenance, f) =>
+                    {
+                        for line in f.lines.iter_mut() {
+                            *line = qualify(line, &aliases);
+                        }
+                    }
+                    (Provenance::Generated, Block::Other(text)) => *text = qualify(text, &aliases),
+                    _ => {}
+                }
+            }
+        }
+
+        if page.old_code_at_end && page.provenance.contains(&Provenance::Original) {
+            log::debug!("moved the original functions to the end");
+            let blocks = std::mem::take(&mut page.blocks);
+            let provenance = std::mem::take(&mut page.provenance);
+            let (old, new): (Vec<_>, Vec<_>) = provenance
+                .into_iter()
+                .zip(blocks)
+                .partition(|(provenance, _)| *provenance == Provenance::Original);
+            let header = (Provenance::Original, Block::Other(OLD_CODE.into()));
+            (page.provenance, page.blocks) = new.into_iter().chain(Some(header)).chain(old).unzip();
+        }
+
+        page
+    }
+}
+
+impl Page {
+    /// Remove the import of the module, unless it is still used outside of
+    /// the commented out functions.
+    fn unimport(&mut self, name: &str) {
+        let import = self
+            .blocks
+            .iter()
+            .position(|b| matches!(b, Block::Import(m) if m.name == name && m.alias.is_none()));
+        let used = self.summary().enumerate().any(|(i, (provenance, block))| {
+            Some(i) != import
+                && provenance != Provenance::Original
+                && block
+                    .to_string()
+                    .lines()
+                    .any(|l| words(l).any(|(_, w)| w == name))
+        });
+        if let Some(i) = import.filter(|_| !used) {
+            log::info!("removed the import of {}, which is no longer used", name);
+            self.blocks.remove(i);
+            self.provenance.remove(i);
+        }
+    }
+
+    /// Replace the function by the template, commenting it out below. When
+    /// keeping its body, it only gets the annotation and the arguments of the
+    /// template instead, unless the page type has no such function.
+    fn replace(
+        &mut self,
+        block: fn(Function) -> Block,
+        template: &str,
+        old: Function,
+        (shared, request, user): (bool, bool, bool),
+        keep: bool,
+        convert: Option<fn(&str, &str) -> String>,
+    ) {
+        let kept = Some(template)
+            .filter(|t| keep && !t.is_empty())
+            .and_then(|t| rewritten(t, &old, (shared, request, user)));
+
+        if let Some(mut lines) = kept {
+            log::debug!("kept the body of the {} function", old.name());
+            let function = Function {
+                doc: vec![],
+                lines: lines.clone(),
+            };
+            let template = Function {
+                doc: vec![],
+                lines: template.lines().map(String::from).collect(),
+            };
+            let arity = template.definition().map_or(0, |(_, args)| args.len());
+            let convert = match (convert, function.definition()) {
+                // Its results are functions still waiting for arguments
+                (Some(_), Some((_, args))) if args.len() < arity => {
+                    log::warn!(
+                        "{} is defined point-free, rewrite what it results in by hand",
+                        old.name()
+                    );
+                    None
+                }
+                (convert, definition) => convert.zip(definition),
+            };
+            if let Some((convert, (line, _))) = convert {
+                let text = lines[line..].join("\n");
+                if let Some((definition, body)) = text.split_once('=') {
+                    let body = convert(old.name(), body);
+                    let text = format!("{}={}", definition, body);
+                    lines.truncate(line);
+                    lines.extend(text.split('\n').map(String::from));
+                }
+            }
+            self.rewritten.push(old.name().to_string());
+            self.provenance.push(Provenance::Preserved);
+            self.blocks.push(block(Function {
+                lines,
+                doc: old.doc,
+            }));
+            return;
+        }
+
+        self.push(
+            Provenance::Generated,
+            block(Function {
+                lines: named_like(template, &old),
+                doc: old.doc.clone(),
+            }),
+        );
+        self.supersede(&old);
+    }
+
+    /// Leave the replaced function commented out, unless deleting it or
+    /// moving it out of the page.
+    fn supersede(&mut self, old: &Function) {
+        if self.remove_old {
+            log::debug!("removed the original {} function", old.name());
+            return;
+        }
+        if self.old_code_to_sidecar {
+            log::debug!("moved the original {} function out", old.name());
+            let end = old
+                .lines
+                .iter()
+                .rposition(|l| !l.trim().is_empty())
+                .map_or(0, |i| i + 1);
+            self.old_code.push(old.lines[..end].join("\n"));
+            return;
+        }
+        let text = match self.comment_style {
+            CommentStyle::Line => commented(&old.lines, self.marker()),
+            CommentStyle::Block => block_commented(&old.lines, self.marker()),
+        };
+        self.push(Provenance::Original, Block::Other(text));
+    }
+}
+
+/// The module documentation with its `@docs` lines listing what the module
+/// exposes, unless they already do or there are none.
+fn documented(doc: &str, exposing: &Exposing) -> Option<String> {
+    if exposing.everything() {
+        return None;
+    }
+    let exposed: Vec<&str> = exposing
+        .names()
+        .map(|n| n.strip_suffix("(..)").unwrap_or(n))
+        .collect();
+
+    let mut lines: Vec<String> = vec![];
+    let mut listed = vec![];
+    let mut last = None;
+    for line in doc.split('\n') {
+        let Some(names) = line.trim_start().strip_prefix("@docs") else {
+            lines.push(line.to_string());
+            continue;
+        };
+        let indent = &line[..line.len() - line.trim_start().len()];
+        let kept: Vec<&str> = names
+            .split(',')
+            .map(str::trim)
+            .filter(|n| !n.is_empty())
+            .filter(|n| exposed.contains(n))
+            .collect();
+        listed.extend(names.split(',').map(str::trim).map(String::from));
+        if !kept.is_empty() {
+            last = Some(lines.len());
+            lines.push(format!("{}@docs {}", indent, kept.join(", ")));
+        }
+    }
+    if listed.is_empty() {
+        return None;
+    }
+
+    let missing: Vec<&str> = exposed
+        .iter()
+        .copied()
+        .filter(|n| !listed.iter().any(|l| l == n))
+        .collect();
+    if !missing.is_empty() {
+        match last {
+            Some(i) => lines[i] = format!("{}, {}", lines[i], missing.join(", ")),
+            None => {
+                let end = lines
+                    .iter()
+                    .rposition(|l| l.contains("-}"))
+                    .unwrap_or(lines.len());
+                lines.insert(end, format!("@docs {}", missing.join(", ")));
+            }
+        }
+    }
+
+    let updated = lines.join("\n");
+    Some(updated).filter(|u| u != doc)
+}
+
+/// Heading of the section the replaced functions are moved to.
+const OLD_CODE: &str =
+    "-- OLD CODE ----------------------------------------------------------------
+--
+-- The functions replaced while migrating, kept for reference. Delete them
+-- once the new ones do what they used to.";
+
+/// Comment out the lines of a function with the marker, leaving out its
+/// trailing blank lines.
+fn commented(lines: &[String], marker: &str) -> String {
+    let end = lines
+        .iter()
+        .rposition(|l| !l.trim().is_empty())
+        .map_or(0, |i| i + 1);
+    lines[..end]
+        .iter()
+        .map(|l| format!("{} {}", marker, l))
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// Comment out the lines of a function with a single block comment, opened
+/// with what follows the `--` of the marker, leaving out its trailing blank
+/// lines. The `{-` and `-}` in the function, e.g. in its comments or strings,
+/// get a space inserted so that they can't end the comment early.
+fn block_commented(lines: &[String], marker: &str) -> String {
+    let end = lines
+        .iter()
+        .rposition(|l| !l.trim().is_empty())
+        .map_or(0, |i| i + 1);
+    let lines = lines[..end]
+        .iter()
+        .map(|l| l.replace("{-", "{ -").replace("-}", "- }"))
+        .collect::<Vec<String>>();
+    let marker = marker.trim_start_matches('-').replace("-}", "- }");
+    format!("{{-{}\n{}\n-}}", marker.trim_end(), lines.join("\n"))
+}
+
+/// The lines of the template, with its arguments named the way they were in
+/// the function it replaces, e.g. `page _ request =` instead of
+/// `page shared req =`. Only simple names are reused, and only if the
+/// function took as many arguments and the names don't clash with others.
+fn named_like(template: &str, old: &Function) -> Vec<String> {
+    let mut lines: Vec<String> = template.lines().map(String::from).collect();
+    let new = Function {
+        doc: vec![],
+        lines: lines.clone(),
+    };
+
+    let (Some((line, args)), Some((_, old_args))) = (new.definition(), old.definition()) else {
+        return lines;
+    };
+    if args.len() != old_args.len() {
+        return lines;
+    }
+
+    let is_name = |w: &str| {
+        w.starts_with(|c: char| c.is_lowercase() || c == '_')
+            && w.chars().all(|c| c.is_alphanumeric() || c == '_')
+    };
+    let used = |name: &str, skip: Option<usize>| {
+        lines
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| Some(*i) != skip)
+            .any(|(_, l)| words(l).any(|(_, w)| w == name))
+    };
+
+    let mut renames: Vec<(String, String)> = vec![];
+    for (arg, old_arg) in args.iter().zip(old_args.iter()) {
+        if arg == old_arg || !is_name(old_arg) {
+            continue;
+        }
+        // An argument left unnamed can't be used by the template
+        let clash = match *old_arg {
+            "_" => used(arg, Some(line)),
+            _ => used(old_arg, None),
+        };
+        if clash {
+            continue;
+        }
+        renames.push((arg.to_string(), old_arg.to_string()));
+    }
+
+    for (i, l) in lines.iter_mut().enumerate() {
+        let mut result = String::with_capacity(l.len());
+        let mut last = 0;
+        for (start, word) in words(l) {
+            let renamed = renames
+                .iter()
+                .find(|(from, to)| from == word && (to != "_" || i == line));
+            if let Some((_, to)) = renamed {
+                result.push_str(&l[last..start]);
+                result.push_str(to);
+                last = start + word.len();
+            }
+        }
+        result.push_str(&l[last..]);
+        *l = result;
+    }
+    lines
+}
+
+/// The function with the annotation of the template, and its body taking
+/// the arguments of the template, e.g. `view shared model =` instead of
+/// `view model =`. The shared model and the request are added or dropped as
+/// the template says, and the other arguments keep their names.
+fn rewritten(
+    template: &str,
+    old: &Function,
+    (shared, request, user): (bool, bool, bool),
+) -> Option<Vec<String>> {
+    let new = Function {
+        doc: vec![],
+        lines: template.lines().map(String::from).collect(),
+    };
+    let (line, args) = new.definition()?;
+    let (old_line, old_args) = old.definition()?;
+    let (old_shared, old_request) = old.arguments();
+
+    // The user, the shared model and the request come first
+    let mut old_args = old_args.into_iter();
+    let old_user = if old.takes_user() {
+        old_args.next()
+    } else {
+        None
+    };
+    let old_shared = if old_shared { old_args.next() } else { None };
+    let old_request = if old_request { old_args.next() } else { None };
+
+    let mut names: Vec<&str> = vec![new.name()];
+    if user {
+        names.push(old_user.or(args.first().copied())?);
+    }
+    if shared {
+        names.push(old_shared.or(args.get(user as usize).copied())?);
+    }
+    if request {
+        names.push(old_request.or(args.get(user as usize + shared as usize).copied())?);
+    }
+    // Those the template doesn't take are dropped, but one the body needs
+    // may be left out of a point-free definition
+    let own = args.len() - user as usize - shared as usize - request as usize;
+    let old_args: Vec<&str> = old_args.collect();
+    let (kept, extra) = old_args.split_at(own.min(old_args.len()));
+    names.extend(kept);
+
+    let (_, rest) = old.lines[old_line].split_once('=')?;
+    let mut lines: Vec<String> = new.lines[..line].to_vec();
+    lines.push(format!("{} ={}", names.join(" "), rest));
+    lines.extend(old.lines[old_line + 1..].iter().cloned());
+
+    let mut dropped: Vec<&str> = extra.to_vec();
+    dropped.extend(old_request.filter(|_| !request));
+    dropped.extend(old_shared.filter(|_| !shared));
+    dropped.extend(old_user.filter(|_| !user));
+    for name in dropped.into_iter().filter(|&n| n != "_") {
+        let body = std::iter::once(rest).chain(lines[line + 1..].iter().map(String::as_str));
+        if body.flat_map(words).any(|(_, w)| w == name) {
+            log::warn!(
+                "{} no longer takes {}, but its body still uses it",
+                new.name(),
+                name
+            );
+        }
+    }
+    Some(lines)
+}
+
+/// Pass the user, the shared model and the request where the function calls
+/// the page functions that didn't take them before, e.g. `init` in `update`
+/// becoming `init shared`. They get passed along under the names the function
+/// gives them.
+fn threaded(
+    function: &mut Function,
+    callees: &[(String, bool, bool, bool)],
+    (shared, request, user): (bool, bool, bool),
+) {
+    let Some((line, args)) = function.definition() else {
+        return;
+    };
+    let user_name = args.first().filter(|_| user).map(|a| a.to_string());
+    let shared_name = args
+        .get(user as usize)
+        .filter(|_| shared)
+        .map(|a| a.to_string());
+    let request_name = args
+        .get(user as usize + shared as usize)
+        .filter(|_| request)
+        .map(|a| a.to_string());
+
+    for (callee, had_user, had_shared, had_request) in callees {
+        // Right after the name, or after the shared model it already takes
+        let mut first = vec![];
+        let mut second = vec![];
+        if !had_user {
+            first.extend(user_name.clone());
+        }
+        if !had_shared {
+            first.extend(shared_name.clone());
+        }
+        if !had_request && *had_shared {
+            second.extend(request_name.clone());
+        } else if !had_request {
+            first.extend(request_name.clone());
+        }
+        if first.is_empty() && second.is_empty() {
+            continue;
+        }
+
+        let mut text = function.lines[line..].join("\n");
+        let body = text.find('=').map_or(text.len(), |e| e + 1);
+        let tokens = syntax::tokens(&text);
+        let mut inserts: Vec<(usize, String)> = vec![];
+        for (n, token) in tokens.iter().enumerate() {
+            let next = tokens.get(n + 1);
+            let field = next.is_some_and(|t| t.text == "=");
+            if token.text != callee || token.span.start < body || field {
+                continue;
+            }
+            if !first.is_empty() {
+                inserts.push((token.span.end, format!(" {}", first.join(" "))));
+            }
+            if let Some(next) = next.filter(|t| !second.is_empty() && is_argument(t.text)) {
+                inserts.push((next.span.end, format!(" {}", second.join(" "))));
+            }
+        }
+        if inserts.is_empty() {
+            continue;
+        }
+
+        for (at, inserted) in inserts.into_iter().rev() {
+            text.insert_str(at, &inserted);
+        }
+        function.lines.truncate(line);
+        function.lines.extend(text.split('\n').map(String::from));
+    }
+}
+
+/// Whether the token is a plain name that can be passed as an argument.
+fn is_argument(token: &str) -> bool {
+    token.starts_with(|c: char| c.is_lowercase() || c == '_')
+        && token.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// The unqualified names in the line, and where they start.
+fn words(line: &str) -> impl Iterator<Item = (usize, &str)> {
+    let is_name_char = |c: char| c.is_alphanumeric() || c == '_';
+    line.char_indices()
+        .filter(move |&(i, c)| {
+            is_name_char(c)
+                && !line[..i]
+                    .chars()
+                    .next_back()
+                    .is_some_and(|p| is_name_char(p) || p == '.')
+        })
+        .map(move |(i, _)| {
+            let end = line[i..]
+                .find(|c: char| !is_name_char(c))
+                .map_or(line.len(), |e| i + e);
+            (i, &line[i..end])
+        })
+}
+
+/// Replace the module qualifiers, e.g. `Request.With`, by their aliases.
+fn qualify(text: &str, aliases: &[(String, String)]) -> String {
+    let mut text = text.to_string();
+    for (name, alias) in aliases {
+        let mut result = String::with_capacity(text.len());
+        let mut last = 0;
+        for (i, _) in text.match_indices(name.as_str()) {
+            let qualified = text[..i]
+                .chars()
+                .next_back()
+                .is_some_and(|c| c.is_alphanumeric() || c == '_' || c == '.');
+            if !qualified {
+                result.push_str(&text[last..i]);
+                result.push_str(alias);
+                last = i + name.len();
+            }
+        }
+        result.push_str(&text[last..]);
+        text = result;
+    }
+    text
+}
+
+/// Log what the migration does to each block.
+fn log_block(provenance: Provenance, block: &Block) {
+    match (provenance, block) {
+        (Provenance::Preserved, _) => {}
+        (Provenance::Generated, Block::Module(m)) => log::debug!(
+            "exposing{} from module {}",
+            m.exposing
+                .as_ref()
+                .map(ToString::to_string)
+                .unwrap_or_default(),
+            m.name
+        ),
+        (Provenance::Generated, Block::Import(m)) => log::debug!("injected import {}", m.name),
+        (Provenance::Generated, Block::Init(f))
+        | (Provenance::Generated, Block::Update(f))
+        | (Provenance::Generated, Block::View(f))
+        | (Provenance::Generated, Block::Subscriptions(f))
+        | (Provenance::Generated, Block::Page(f)) => {
+            log::debug!("replaced {} function with the template", f.name())
+        }
+        (Provenance::Generated, Block::Port(_))
+        | (Provenance::Generated, Block::Type(_))
+        | (Provenance::Generated, Block::Alias(_)) => {}
+        (Provenance::Generated, Block::Other(text)) => log::debug!(
+            "appended stub {}",
+            text.trim().lines().next().unwrap_or_default()
+        ),
+        (Provenance::Original, _) => log::trace!("commented out the original function"),
+    }
+}
+
+impl Page {
+    fn push(&mut self, provenance: Provenance, block: Block) {
+        log_block(provenance, &block);
+        self.provenance.push(provenance);
+        self.blocks.push(block);
+    }
+
+    fn insert(&mut self, index: usize, provenance: Provenance, block: Block) {
+        log_block(provenance, &block);
+        self.provenance.insert(index, provenance);
+        self.blocks.insert(index, block);
+    }
+
+    /// Summary of where each block came from, in output order.
+    pub fn summary(&self) -> impl Iterator<Item = (Provenance, &Block)> {
+        self.provenance.iter().copied().zip(self.blocks.iter())
+    }
+
+    /// Render the page with a `{- provenance -}` comment wherever the origin
+    /// of the blocks changes.
+    pub fn annotated(&self) -> String {
+        let mut out = String::new();
+        let mut last = None;
+        for (provenance, text) in self.rendered() {
+            let content = text.trim_start_matches('\n');
+            out.push_str(&text[..text.len() - content.len()]);
+            if !content.trim().is_empty() && last != Some(provenance) {
+                out.push_str(&format!("{{- {} -}}\n", provenance));
+                last = Some(provenance);
+            }
+            out.push_str(content);
+        }
+        self.ending(out)
+    }
+
+    /// The text of each block. What the migration adds or rewrites is set
+    /// apart from its surroundings by a blank line, unless there already is
+    /// one, so that migrating again doesn't pile them up.
+    fn rendered(&self) -> Vec<(Provenance, String)> {
+        let mut rendered = vec![];
+        let mut out = String::new();
+        let mut apart = false;
+        for (provenance, block) in self.summary() {
+            let rewritten = block
+                .function()
+                .is_some_and(|f| self.rewritten.iter().any(|r| r == f.name()));
+            let added = (provenance != Provenance::Preserved || rewritten)
+                && !matches!(block, Block::Module(_) | Block::Import(_));
+
+            let mut text = block.to_string();
+            if added {
+                text = format!("{}\n", text.trim_matches('\n'));
+            }
+            if (added || apart)
+                && !out.is_empty()
+                && !out.ends_with("\n\n")
+                && !text.starts_with('\n')
+            {
+                text.insert(0, '\n');
+            }
+
+            apart = added;
+            out.push_str(&text);
+            rendered.push((provenance, text));
+        }
+        rendered
+    }
+
+    /// End the text the way the original did if it ends with what was
+    /// preserved from it, or else with a single newline.
+    fn ending(&self, mut text: String) -> String {
+        if self.bom {
+            text.insert(0, '\u{feff}');
+        }
+
+        match self.provenance.last() {
+            Some(Provenance::Preserved) if self.unterminated => {
+                text.pop();
+            }
+            Some(Provenance::Preserved) | None => {}
+            Some(_) => {
+                text.truncate(text.trim_end_matches('\n').len());
+                text.push('\n');
+            }
+        }
+
+        match self.line_ending {
+            LineEnding::Lf => text,
+            LineEnding::Crlf => text.replace('\n', "\r\n"),
+        }
+    }
+
+    /// Keep the bodies of the page functions when migrating, only rewriting
+    /// their annotations and arguments.
+    pub fn preserving_bodies(mut self) -> Self {
+        self.preserve_bodies = true;
+        self
+    }
+
+    /// Delete the replaced functions when migrating, instead of commenting
+    /// them out.
+    pub fn removing_old(mut self) -> Self {
+        self.remove_old = true;
+        self
+    }
+
+    /// Move the replaced functions to the end of the page when migrating,
+    /// instead of leaving them below their replacements.
+    pub fn moving_old_to_end(mut self) -> Self {
+        self.old_code_at_end = true;
+        self
+    }
+
+    /// Move the replaced functions out of the page when migrating, to be
+    /// written to a file of their own.
+    pub fn moving_old_to_sidecar(mut self) -> Self {
+        self.old_code_to_sidecar = true;
+        self
+    }
+
+    /// The replaced functions moved out of the page, if any.
+    pub fn old_code(&self) -> Option<String> {
+        if self.old_code.is_empty() {
+            return None;
+        }
+        Some(format!("{}\n", self.old_code.join("\n\n\n")))
+    }
+
+    /// Make it a protected page when migrating, passing the signed in user to
+    /// the page functions.
+    pub fn protecting(mut self) -> Self {
+        self.protected = true;
+        self
+    }
+
+    /// Pass the signed in user to the page functions when migrating.
+    pub fn passing_user(mut self) -> Self {
+        self.user = true;
+        self
+    }
+
+    /// Start the init template with an example of reading the query of the
+    /// request when migrating.
+    pub fn reading_query(mut self) -> Self {
+        self.with_query = true;
+        self
+    }
+
+    /// Leave the page functions as they are when migrating.
+    pub fn leaving(mut self, names: &[&str]) -> Self {
+        self.untouched.extend(names.iter().map(|n| n.to_string()));
+        self
+    }
+
+    /// Print the page with the given line ending instead of the one it was
+    /// read with.
+    pub fn with_line_ending(mut self, line_ending: LineEnding) -> Self {
+        self.line_ending = line_ending;
+        self
+    }
+
+    /// Comment out the replaced functions with the marker instead of `--`
+    /// when migrating.
+    pub fn with_marker(mut self, marker: &str) -> Self {
+        self.marker = Some(marker.into());
+        self
+    }
+
+    fn marker(&self) -> &str {
+        self.marker.as_deref().unwrap_or("--")
+    }
+
+    /// Comment out the replaced functions in the given style when migrating.
+    pub fn with_comment_style(mut self, comment_style: CommentStyle) -> Self {
+        self.comment_style = comment_style;
+        self
+    }
+
+    /// Read the page type from the `Page.<type>` call in the `page` function,
+    /// and whether the shared model and the request are passed down to the
+    /// page functions.
+    pub fn shape(&self) -> Option<Shape> {
+        let function = self.blocks.iter().find_map(|b| match b {
+            Block::Page(f) => Some(f),
+            _ => None,
+        })?;
+        let lines = &function.lines;
+
+        let (pagetype, protected) = lines.iter().find_map(|l| {
+            l.split_whitespace()
+                .find_map(|w| w.strip_prefix("Page."))
+                .and_then(|w| match w.strip_prefix("protected.") {
+                    Some(w) => PageType::from(w).map(|t| (t, true)),
+                    None => PageType::from(w).map(|t| (t, false)),
+                })
+        })?;
+
+        // The argument names are taken from `page shared req =`
+        let args = function
+            .definition()
+            .map(|(_, args)| args)
+            .unwrap_or_default();
+
+        // And the wiring from fields like `, view = view shared req`
+        let passes = |arg: Option<&&str>| {
+            arg.filter(|a| a != &&"_")
+                .map(|arg| {
+                    lines.iter().any(|l| {
+                        l.split_once('=')
+                            .filter(|(field, _)| field.trim_start().starts_with(['{', ',']))
+                            .map(|(_, value)| value.split_whitespace().skip(1).any(|w| w == *arg))
+                            .unwrap_or(false)
+                    })
+                })
+                .unwrap_or(false)
+        };
+
+        Some(Shape {
+            pagetype,
+            shared: passes(args.first()),
+            request: passes(args.get(1)),
+            user: passes(Some(&"user")),
+            protected,
+        })
+    }
+
+    /// The page functions defined in the page, along with whether each one
+    /// takes the shared model and the request.
+    pub fn arguments(&self) -> Vec<(&str, bool, bool)> {
+        self.blocks
+            .iter()
+            .filter_map(|b| match b {
+                Block::Init(f) | Block::Update(f) | Block::View(f) | Block::Subscriptions(f) => {
+                    let (shared, request) = f.arguments();
+                    Some((f.name(), shared, request))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Whether the page still contains page functions commented out by a
+    /// previous migration.
+    pub fn has_leftovers(&self) -> bool {
+        let marker = self.marker();
+        let block = block_commented(&[], marker);
+        let opening = block.lines().next();
+        let is_page_function = |name: Option<&str>| {
+            name.map(|name| PAGE_FUNCTIONS.contains(&name))
+                .unwrap_or(false)
+        };
+        self.blocks.iter().any(|b| match b {
+            Block::Other(text) if text.lines().next() == opening => {
+                text.lines().any(|l| is_page_function(l.split(' ').next()))
+            }
+            Block::Other(text) => text.lines().any(|l| {
+                is_page_function(
+                    l.strip_prefix(marker)
+                        .filter(|l| l.starts_with(' '))
+                        .and_then(|l| l.split_whitespace().next()),
+                )
+            }),
+            _ => false,
+        })
+    }
+
+    /// Whether the page declares the type, as a custom type or an alias, or
+    /// imports it by name.
+    fn has_type(&self, name: &str) -> bool {
+        self.blocks.iter().any(|b| match b {
+            Block::Type(t) => t.name == name,
+            Block::Alias(a) => a.name == name,
+            Block::Import(Module {
+                exposing: Some(exposing),
+                ..
+            }) => exposing.items.iter().any(|i| i.is(name)),
+            _ => false,
+        })
+    }
+
+    /// What could not be parsed and was kept as is, in order.
+    pub fn problems(&self) -> &[ParseError] {
+        &self.problems
+    }
+
+    pub fn module_name(&self) -> Option<&str> {
+        self.blocks.iter().find_map(|b| match b {
+            Block::Module(m) => Some(m.name.as_str()),
+            _ => None,
+        })
+    }
+
+    pub fn set_module_name(&mut self, name: &str) {
+        if let Some(m) = self.blocks.iter_mut().find_map(|b| match b {
+            Block::Module(m) => Some(m),
+            _ => None,
+        }) {
+            m.name = name.to_string();
+            m.source = None;
+        }
+    }
+}
+
+/// Refuse to migrate modules that are never elm-spa pages, e.g. the generated
+/// `Gen.*` modules, the top-level `Main` and `Shared` modules, or components
+/// that are neither named `Pages.*` nor live under a `Pages` directory,
+/// unless forced.
+pub fn guard(path: &Path, page: &Page, force: bool) -> Result<()> {
+    if force {
+        return Ok(());
+    }
+
+    let pages_path = modules::from_path(path).is_some();
+    let generated_path = path.components().any(|c| c.as_os_str() == "Gen");
+    let reserved_path = path
+        .file_name()
+        .map(|f| f == "Main.elm" || f == "Shared.elm")
+        .unwrap_or(false)
+        && !pages_path;
+    let pages_module = page
+        .module_name()
+        .is_some_and(|name| name.starts_with("Pages."));
+
+    let (generated_module, reserved_module) = match page.module_name() {
+        Some(name) => (name.starts_with("Gen."), name == "Main" || name == "Shared"),
+        None => (false, false),
+    };
+
+    if generated_path || generated_module {
+        bail!(
+            "refusing to migrate a module generated by elm-spa, it is not a page\n\
+            Pass --force if you really want to migrate it."
+        );
+    } else if reserved_path || reserved_module {
+        bail!(
+            "refusing to migrate a module that is not an elm-spa page\n\
+            Pass --force if you really want to migrate it."
+        );
+    } else if !pages_path && !pages_module {
+        bail!(
+            "refusing to migrate {}, pages are named Pages.* and live under src/Pages\n\
+            Pass --force if you really want to migrate it.",
+            page.module_name().unwrap_or("a module without a header")
+        );
+    }
+
+    Ok(())
+}
+
+impl fmt::Display for Page {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> std::fmt::Result {
+        let text = self.rendered().into_iter().map(|(_, text)| text).collect();
+        write!(f, "{}", self.ending(text))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn guard_refuses_generated_modules_unless_forced() {
+        let page = Page::parse("module Gen.Route exposing (Route)\n\n\ntype Route\n    = Home_\n")
+            .unwrap();
+        let path = Path::new("src/Gen/Route.elm");
+
+        let error = guard(path, &page, false).unwrap_err();
+        assert!(error.to_string().contains("generated by elm-spa"));
+        assert!(guard(path, &page, true).is_ok());
+    }
+
+    #[test]
+    fn annotated_labels_kept_generated_and_commented_blocks() {
+        let text = "module Pages.About exposing (page)\n\n\
+            import Page exposing (Page)\n\
+            import View exposing (View)\n\n\n\
+            page : Page\n\
+            page =\n    Page.static\n        { view = view\n        }\n\n\n\
+            view : View msg\n\
+            view =\n    View.placeholder \"About\"\n";
+        let annotated = Page::parse(text)
+            .unwrap()
+            .to(PageType::Element, false, false)
+            .annotated();
+
+        assert!(annotated.contains("{- generated -}\npage : Shared.Model"));
+        assert!(annotated.contains("{- original (commented) -}\n-- page : Page\n"));
+        assert!(annotated.contains(
+            "{- preserved -}\nview :   Model -> View Msg\nview model =\n    View.placeholder"
+        ));
+
+        // Bodies kept by --preserve-bodies are the user's own code
+        let text = "module Pages.Counter exposing (Model, Msg, page)\n\n\
+            import Page exposing (Page)\n\
+            import View exposing (View)\n\n\n\
+            page : Page.With Model Msg\n\
+            page =\n    Page.sandbox\n        { init = init\n        , update = update\n        , view = view\n        }\n\n\n\
+            type alias Model =\n    Int\n\n\n\
+            init : Model\n\
+            init =\n    0\n\n\n\
+            type Msg\n    = Increment\n\n\n\
+            update : Msg -> Model -> Model\n\
+            update msg model =\n    model + 1\n\n\n\
+            view : Model -> View Msg\n\
+            view model =\n    View.placeholder \"Counter\"\n";
+        let annotated = Page::parse(text)
+            .unwrap()
+            .preserving_bodies()
+            .to(PageType::Element, false, false)
+            .annotated();
+        let (_, preserved) = annotated
+            .split_once("{- preserved -}\ntype alias Model")
+            .unwrap();
+        let (preserved, generated) = preserved.split_once("{- generated -}").unwrap();
+
+        assert!(preserved.contains("init =\n    ( 0, Cmd.none )"));
+        assert!(preserved.contains("update msg model =\n    ( model + 1, Cmd.none )"));
+        assert!(generated.starts_with("\nsubscriptions :"));
+    }
+
+    #[test]
+    fn passing_user_keeps_the_page_unprotected() {
+        let text = "module Pages.About exposing (page)\n\n\
+            import Page exposing (Page)\n\
+            import View exposing (View)\n\n\n\
+            page : Page\n\
+            page =\n    Page.static\n        { view = view\n        }\n";
+
+        for (pagetype, function) in [
+            (PageType::Sandbox, "Page.sandbox\n"),
+            (PageType::Element, "Page.element\n"),
+        ] {
+            let page = Page::parse(text)
+                .unwrap()
+                .passing_user()
+                .to(pagetype, false, false)
+                .to_string();
+
+            assert!(page.contains(function));
+            assert!(!page.contains("Page.protected"));
+            assert!(page.contains("import Auth\n"));
+            assert!(page.contains("init : Auth.User -> "));
+            assert!(page.contains("init = init user"));
+        }
+    }
+}
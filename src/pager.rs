@@ -0,0 +1,47 @@
+use anyhow::Result;
+use ratatui::crossterm::terminal;
+use std::env;
+use std::io::prelude::*;
+use std::io::{stdout, IsTerminal};
+use std::process::{Command, Stdio};
+
+/// Whether output is worth paging at all, i.e. it goes to a terminal.
+pub fn wanted() -> bool {
+    stdout().is_terminal()
+}
+
+/// Print the text, through `$PAGER` (or `less`) if it doesn't fit on the
+/// screen.
+pub fn print(text: &str) -> Result<()> {
+    let rows = terminal::size().map(|(_, r)| r as usize).unwrap_or(0);
+    if rows == 0 || text.lines().count() < rows {
+        print!("{}", text);
+        return Ok(());
+    }
+
+    let pager = env::var("PAGER").unwrap_or_else(|_| "less -R".into());
+    let mut words = pager.split_whitespace();
+    let spawned = match words.next() {
+        Some(program) => Command::new(program)
+            .args(words)
+            .stdin(Stdio::piped())
+            .spawn()
+            .ok(),
+        None => None,
+    };
+
+    let mut child = match spawned {
+        Some(child) => child,
+        None => {
+            print!("{}", text);
+            return Ok(());
+        }
+    };
+
+    if let Some(mut stdin) = child.stdin.take() {
+        // The pager may be quit before reading everything
+        let _ = stdin.write_all(text.as_bytes());
+    }
+    child.wait()?;
+    Ok(())
+}
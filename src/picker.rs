@@ -0,0 +1,156 @@
+use anyhow::{bail, Result};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, List, ListItem, ListState};
+use ratatui::{DefaultTerminal, Frame};
+use std::path::PathBuf;
+
+/// Score how well the query fuzzy matches the candidate, if at all. Every
+/// character of the query must appear in order. Consecutive matches and
+/// matches at the start of a module segment score higher.
+fn score(query: &str, candidate: &str) -> Option<usize> {
+    let mut score = 0;
+    let mut previous: Option<usize> = None;
+    let mut chars = candidate.char_indices();
+
+    for q in query.chars().flat_map(char::to_lowercase) {
+        let (i, _) = chars
+            .by_ref()
+            .find(|(_, c)| c.to_lowercase().any(|c| c == q))?;
+
+        score += 1;
+        if previous.map(|p| p + 1 == i).unwrap_or(false) {
+            score += 5;
+        }
+        if i == 0 || candidate[..i].ends_with('.') {
+            score += 10;
+        }
+        previous = Some(i);
+    }
+
+    Some(score)
+}
+
+struct Picker {
+    candidates: Vec<(String, PathBuf)>,
+    query: String,
+    matches: Vec<usize>,
+    list: ListState,
+}
+
+impl Picker {
+    fn filter(&mut self) {
+        let mut scored: Vec<(usize, usize)> = self
+            .candidates
+            .iter()
+            .enumerate()
+            .filter_map(|(i, (name, _))| score(&self.query, name).map(|s| (s, i)))
+            .collect();
+
+        scored.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+        self.matches = scored.into_iter().map(|(_, i)| i).collect();
+        self.list.select(if self.matches.is_empty() {
+            None
+        } else {
+            Some(0)
+        });
+    }
+
+    fn select(&mut self, offset: isize) {
+        if self.matches.is_empty() {
+            return;
+        }
+        let last = self.matches.len() as isize - 1;
+        let current = self.list.selected().unwrap_or_default() as isize;
+        self.list
+            .select(Some((current + offset).clamp(0, last) as usize));
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [input, list] =
+            Layout::vertical([Constraint::Length(3), Constraint::Min(1)]).areas(frame.area());
+
+        let block = Block::bordered().title(" Pick a page ");
+        let prompt = block.inner(input);
+        frame.render_widget(block, input);
+        frame.render_widget(Line::from(format!("> {}", self.query)), prompt);
+
+        let candidates = &self.candidates;
+        let items: Vec<ListItem> = self
+            .matches
+            .iter()
+            .map(|&i| ListItem::new(candidates[i].0.as_str()))
+            .collect();
+
+        let widget = List::new(items)
+            .block(Block::bordered().title(format!(
+                " {}/{} ",
+                self.matches.len(),
+                candidates.len()
+            )))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(widget, list, &mut self.list);
+    }
+
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> Result<Option<PathBuf>> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+
+            let key = match event::read()? {
+                Event::Key(key) if key.kind == KeyEventKind::Press => key,
+                _ => continue,
+            };
+
+            match key.code {
+                KeyCode::Esc => return Ok(None),
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    return Ok(None)
+                }
+                KeyCode::Enter => {
+                    return Ok(self
+                        .list
+                        .selected()
+                        .and_then(|i| self.matches.get(i))
+                        .map(|&i| self.candidates[i].1.clone()))
+                }
+                KeyCode::Down => self.select(1),
+                KeyCode::Up => self.select(-1),
+                KeyCode::Backspace => {
+                    self.query.pop();
+                    self.filter();
+                }
+                KeyCode::Char(c) => {
+                    self.query.push(c);
+                    self.filter();
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Let the user fuzzy find one of the pages by its module name.
+pub fn pick(candidates: Vec<(String, PathBuf)>) -> Result<PathBuf> {
+    if candidates.is_empty() {
+        bail!("no pages to pick from");
+    }
+
+    let mut picker = Picker {
+        candidates,
+        query: String::new(),
+        matches: vec![],
+        list: ListState::default(),
+    };
+    picker.filter();
+
+    let mut terminal = ratatui::try_init()?;
+    let picked = picker.run(&mut terminal);
+    ratatui::restore();
+
+    match picked? {
+        Some(path) => Ok(path),
+        None => bail!("no page was picked"),
+    }
+}
@@ -0,0 +1,123 @@
+use crate::cli::Cli;
+use crate::logger;
+use ratatui::crossterm::terminal;
+use std::io::prelude::*;
+use std::io::{stderr, IsTerminal};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+const WIDTH: usize = 20;
+
+/// A progress bar for batch runs, drawn on stderr when it is a terminal, or
+/// else a plain line with the counts once done.
+pub struct Progress {
+    enabled: bool,
+    terminal: bool,
+    quiet: bool,
+    total: usize,
+    done: usize,
+    changed: usize,
+    skipped: usize,
+    failed: usize,
+    started: Instant,
+}
+
+impl Progress {
+    pub fn new(cli: &Cli, total: usize) -> Self {
+        Self {
+            // Log messages would get mixed up with the bar, and so would the
+            // JSON lines of the warnings
+            enabled: total > 1 && cli.verbose == 0 && cli.warnings == logger::Format::Text,
+            terminal: stderr().is_terminal(),
+            quiet: cli.quiet,
+            total,
+            done: 0,
+            changed: 0,
+            skipped: 0,
+            failed: 0,
+            started: Instant::now(),
+        }
+    }
+
+    fn eta(&self) -> Option<Duration> {
+        if self.done == 0 {
+            return None;
+        }
+        let each = self.started.elapsed() / self.done as u32;
+        Some(each * (self.total - self.done) as u32)
+    }
+
+    /// Show the counts so far, along with the file last worked on.
+    pub fn draw(&self, file: &Path) {
+        if !self.enabled || !self.terminal {
+            return;
+        }
+
+        let filled = WIDTH * self.done / self.total.max(1);
+        let mut line = format!(
+            "[{}{}] {}/{} changed {} skipped {} failed {}",
+            "#".repeat(filled),
+            "-".repeat(WIDTH - filled),
+            self.done,
+            self.total,
+            self.changed,
+            self.skipped,
+            self.failed,
+        );
+        if let Some(eta) = self.eta() {
+            line.push_str(&format!(" ETA {}s", eta.as_secs()));
+        }
+        line.push_str(&format!(" {}", file.display()));
+
+        let columns = terminal::size()
+            .ok()
+            .map(|(c, _)| c as usize)
+            .filter(|&c| c > 0)
+            .unwrap_or(80);
+        let line: String = line.chars().take(columns.saturating_sub(1)).collect();
+        eprint!("\r\x1b[2K{}", line);
+        let _ = stderr().flush();
+    }
+
+    pub fn changed(&mut self) {
+        self.changed += 1;
+        self.done += 1;
+    }
+
+    pub fn unchanged(&mut self) {
+        self.done += 1;
+    }
+
+    pub fn skipped(&mut self) {
+        self.skipped += 1;
+        self.done += 1;
+    }
+
+    pub fn failed(&mut self) {
+        self.failed += 1;
+        self.done += 1;
+    }
+
+    /// Clear the bar so that it doesn't get mixed up with what follows, or
+    /// else print the counts.
+    pub fn finish(&self) {
+        if !self.enabled {
+            return;
+        }
+
+        if self.terminal {
+            eprint!("\r\x1b[2K");
+            let _ = stderr().flush();
+        } else if !self.quiet {
+            eprintln!(
+                "{}/{} changed {} skipped {} failed {} in {}s",
+                self.done,
+                self.total,
+                self.changed,
+                self.skipped,
+                self.failed,
+                self.started.elapsed().as_secs()
+            );
+        }
+    }
+}
@@ -0,0 +1,61 @@
+use anyhow::{bail, Result};
+use std::io::prelude::*;
+use std::io::{stderr, stdin};
+
+fn ask(question: &str) -> Result<String> {
+    eprint!("{} ", question);
+    stderr().flush()?;
+
+    let mut answer = String::new();
+    if stdin().read_line(&mut answer)? == 0 {
+        bail!("no answer given");
+    }
+    Ok(answer.trim().to_lowercase())
+}
+
+/// Ask to pick one of the options, either by number or by name.
+pub fn select<T: Copy>(question: &str, options: &[(&str, T)]) -> Result<T> {
+    eprintln!("{}", question);
+    for (i, (name, _)) in options.iter().enumerate() {
+        eprintln!("  {}) {}", i + 1, name);
+    }
+
+    loop {
+        let answer = ask(&format!("Select [1-{}]:", options.len()))?;
+        let picked = answer
+            .parse::<usize>()
+            .ok()
+            .and_then(|n| n.checked_sub(1))
+            .and_then(|i| options.get(i))
+            .or_else(|| options.iter().find(|(name, _)| name == &answer));
+
+        match picked {
+            Some((_, value)) => return Ok(*value),
+            None => eprintln!("Invalid choice: {}", answer),
+        }
+    }
+}
+
+/// Ask to answer with one of the given choices.
+pub fn answer(question: &str, choices: &[&str]) -> Result<String> {
+    loop {
+        let answer = ask(question)?;
+        if choices.contains(&answer.as_str()) {
+            return Ok(answer);
+        }
+        eprintln!("Invalid answer: {}", answer);
+    }
+}
+
+/// Ask a yes/no question.
+pub fn confirm(question: &str, default: bool) -> Result<bool> {
+    let hint = if default { "[Y/n]" } else { "[y/N]" };
+    loop {
+        match ask(&format!("{} {}", question, hint))?.as_str() {
+            "" => return Ok(default),
+            "y" | "yes" => return Ok(true),
+            "n" | "no" => return Ok(false),
+            answer => eprintln!("Invalid answer: {}", answer),
+        }
+    }
+}
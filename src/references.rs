@@ -0,0 +1,82 @@
+use crate::files::discover;
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Byte offsets of the occurrences of a module name in the text that refer to
+/// the module itself, e.g. `Pages.Blog` or `Pages.Blog.page`, but not
+/// `Pages.BlogPost` or the submodule `Pages.Blog.Post`.
+fn occurrences(text: &str, module: &str) -> Vec<usize> {
+    text.match_indices(module)
+        .map(|(i, _)| i)
+        .filter(|&i| {
+            let before = text[..i].chars().next_back();
+            let mut after = text[i + module.len()..].chars();
+            let starts = before.is_none_or(|c| !is_name_char(c) && c != '.');
+            let ends = match after.next() {
+                Some('.') => after.next().is_none_or(|c| !c.is_uppercase()),
+                Some(c) => !is_name_char(c),
+                None => true,
+            };
+            starts && ends
+        })
+        .collect()
+}
+
+/// Replace every reference to the `old` module with the `new` one.
+pub fn replace(text: &str, old: &str, new: &str) -> (String, usize) {
+    let found = occurrences(text, old);
+    let mut result = String::with_capacity(text.len());
+    let mut last = 0;
+    for &i in found.iter() {
+        result.push_str(&text[last..i]);
+        result.push_str(new);
+        last = i + old.len();
+    }
+    result.push_str(&text[last..]);
+    (result, found.len())
+}
+
+/// The lines of the text that refer to the module, numbered from 1.
+pub fn lines(text: &str, module: &str) -> Vec<usize> {
+    let mut lines: Vec<usize> = occurrences(text, module)
+        .into_iter()
+        .map(|i| text[..i].matches('\n').count() + 1)
+        .collect();
+    lines.dedup();
+    lines
+}
+
+/// The source directory a page lives in, i.e. the parent of its `Pages`
+/// directory.
+pub fn source_root(page: &Path) -> PathBuf {
+    let is_pages = |a: &&Path| a.file_name().is_some_and(|n| n == "Pages");
+    page.ancestors()
+        .find(|a| is_pages(a) && a.parent().and_then(Path::file_name) == Some("src".as_ref()))
+        .or_else(|| page.ancestors().find(is_pages))
+        .and_then(Path::parent)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("src"))
+}
+
+/// The files in the source directory, other than the page itself and the
+/// generated modules, that refer to the module.
+pub fn find(root: &Path, page: &Path, module: &str) -> Result<Vec<(PathBuf, Vec<usize>)>> {
+    let mut found = vec![];
+    for file in discover(root)? {
+        // Generated modules get updated by `elm-spa gen`
+        if file == page || file.components().any(|c| c.as_os_str() == "Gen") {
+            continue;
+        }
+
+        let text = std::fs::read_to_string(&file)?;
+        let lines = lines(&text, module);
+        if !lines.is_empty() {
+            found.push((file, lines));
+        }
+    }
+    Ok(found)
+}
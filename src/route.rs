@@ -0,0 +1,186 @@
+//! Mapping between routes like `/users/:id` and page files like
+//! `src/Pages/Users/Id_.elm`, following the elm-spa conventions:
+//!
+//! - `/` is the `Home_` page.
+//! - Static segments are kebab-case in the route and PascalCase in the path,
+//!   e.g. `/about-us` is `AboutUs.elm`.
+//! - Dynamic segments are prefixed with `:` in the route and suffixed with
+//!   `_` in the path, e.g. `/users/:id` is `Users/Id_.elm`.
+//! - A trailing `*` catches the rest of the route, and is `ALL_` in the path,
+//!   e.g. `/docs/*` is `Docs/ALL_.elm`.
+//!
+//! Pages nest as deep as their routes, with any number of dynamic segments,
+//! e.g. `/admin/users/:id/settings/:tab` is
+//! `Admin/Users/Id_/Settings/Tab_.elm`.
+
+use anyhow::{bail, Result};
+use std::path::PathBuf;
+
+pub fn is_route(string: &str) -> bool {
+    string.starts_with('/')
+}
+
+fn to_pascal_case(segment: &str) -> String {
+    segment
+        .split(['-', '_'])
+        .filter(|w| !w.is_empty())
+        .map(|w| {
+            let mut chars = w.chars();
+            chars
+                .next()
+                .map(|c| c.to_uppercase().chain(chars).collect::<String>())
+                .unwrap_or_default()
+        })
+        .collect()
+}
+
+fn to_kebab_case(segment: &str) -> String {
+    let mut kebab = String::new();
+    for (i, c) in segment.chars().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            kebab.push('-');
+        }
+        kebab.extend(c.to_lowercase());
+    }
+    kebab
+}
+
+/// The module name segments of a route, e.g. `/users/:id` is
+/// `["Users", "Id_"]`.
+fn segments(route: &str) -> Result<Vec<String>> {
+    let parts: Vec<&str> = route
+        .trim_start_matches('/')
+        .split('/')
+        .filter(|s| !s.is_empty())
+        .collect();
+
+    if parts.is_empty() {
+        return Ok(vec!["Home_".into()]);
+    }
+
+    let last = parts.len() - 1;
+    parts
+        .into_iter()
+        .enumerate()
+        .map(|(i, part)| {
+            if part == "*" && i == last {
+                return Ok("ALL_".into());
+            } else if part == "*" {
+                bail!("'*' can only end a route, got {}", route);
+            }
+
+            let (dynamic, name) = match part.strip_prefix(':') {
+                Some(name) => (true, name),
+                None => (false, part),
+            };
+
+            let segment = to_pascal_case(name);
+            if segment.is_empty() || !segment.chars().all(|c| c.is_alphanumeric()) {
+                bail!("invalid route segment '{}' in {}", part, route);
+            }
+
+            if dynamic {
+                Ok(format!("{}_", segment))
+            } else {
+                Ok(segment)
+            }
+        })
+        .collect()
+}
+
+/// The page file for a route, e.g. `/users/:id` is `src/Pages/Users/Id_.elm`.
+pub fn to_path(route: &str) -> Result<PathBuf> {
+    let mut path = PathBuf::from("src").join("Pages");
+    path.extend(segments(route)?);
+    path.set_extension("elm");
+    Ok(path)
+}
+
+/// The route of a page module, e.g. `Pages.Users.Id_` is `/users/:id`.
+pub fn from_module(name: &str) -> Option<String> {
+    let rest = name.strip_prefix("Pages.")?;
+    if rest == "Home_" {
+        return Some("/".into());
+    }
+
+    let route = rest
+        .split('.')
+        .map(|segment| match segment.strip_suffix('_') {
+            Some("ALL") => "/*".into(),
+            Some(dynamic) => format!("/:{}", to_kebab_case(dynamic)),
+            None => format!("/{}", to_kebab_case(segment)),
+        })
+        .collect();
+    Some(route)
+}
+
+/// The fields of the params of a page module, one for each of its dynamic
+/// segments, e.g. `id` for `Pages.Users.Id_`, or `all` for a catch-all.
+pub fn params(name: &str) -> Vec<String> {
+    let Some(rest) = name.strip_prefix("Pages.").filter(|&r| r != "Home_") else {
+        return vec![];
+    };
+
+    rest.split('.')
+        .filter_map(|segment| segment.strip_suffix('_'))
+        .map(|dynamic| match dynamic {
+            "ALL" => "all".into(),
+            _ => {
+                let mut chars = dynamic.chars();
+                chars
+                    .next()
+                    .map(|c| c.to_lowercase().chain(chars).collect())
+                    .unwrap_or_default()
+            }
+        })
+        .collect()
+}
+
+/// The module elm-spa generates for the params of a page module. It mirrors
+/// the page module under `Gen.Params`, keeping the trailing `_` of `Home_`,
+/// dynamic segments like `Id_` and catch-all `ALL_` segments, e.g.
+/// `Pages.Users.Id_` is `Gen.Params.Users.Id_`. Only the leading `Pages.` is
+/// dropped, so `Pages.Pages.Foo` is `Gen.Params.Pages.Foo`.
+pub fn params_module(name: &str) -> String {
+    format!("Gen.Params.{}", name.strip_prefix("Pages.").unwrap_or(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::from_path;
+    use std::path::Path;
+
+    #[test]
+    fn params_module_keeps_home_dynamic_and_catch_all_segments() {
+        let params = |path: &str| from_path(Path::new(path)).map(|name| params_module(&name));
+
+        assert_eq!(
+            params("src/Pages/Home_.elm").as_deref(),
+            Some("Gen.Params.Home_")
+        );
+        assert_eq!(
+            params("src/Pages/Users/Id_.elm").as_deref(),
+            Some("Gen.Params.Users.Id_")
+        );
+        assert_eq!(
+            params("src/Pages/Docs/ALL_.elm").as_deref(),
+            Some("Gen.Params.Docs.ALL_")
+        );
+        assert_eq!(params_module("Pages.Pages.Foo"), "Gen.Params.Pages.Foo");
+    }
+
+    #[test]
+    fn deeply_nested_routes_round_trip() {
+        let route = "/admin/users/:user-id/settings/:tab";
+        let module = "Pages.Admin.Users.UserId_.Settings.Tab_";
+
+        assert_eq!(
+            to_path(route).unwrap(),
+            Path::new("src/Pages/Admin/Users/UserId_/Settings/Tab_.elm")
+        );
+        assert_eq!(from_path(&to_path(route).unwrap()).as_deref(), Some(module));
+        assert_eq!(from_module(module).as_deref(), Some(route));
+        assert_eq!(params(module), ["userId", "tab"]);
+    }
+}
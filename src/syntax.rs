@@ -0,0 +1,367 @@
+//! A concrete syntax tree of the top level of an Elm module.
+//!
+//! A module is split into its top-level declarations, each starting at column
+//! 0 and running up to the next one, so that the spans cover the whole text
+//! and nothing gets lost. Comments, strings and GLSL blocks are skipped over
+//! while looking for the starts, so a line like `view model =` inside a block
+//! comment or a multi-line string is not mistaken for a declaration.
+
+use std::ops::Range;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Kind {
+    /// `module`, `port module` or `effect module` header.
+    Module,
+    Import,
+    /// Type annotation of a value, e.g. `view : Model -> View Msg`.
+    Annotation,
+    /// Definition of a value, e.g. `view model =`.
+    Value,
+    /// `type` or `type alias` declaration.
+    Type,
+    Port,
+    Infix,
+    /// Line or block comment starting at column 0.
+    Comment,
+    /// Blank lines before the first declaration.
+    Blank,
+    Other,
+}
+
+#[derive(Debug, Clone)]
+pub struct Decl<'a> {
+    pub kind: Kind,
+    /// Name of the annotated or defined value.
+    pub name: Option<&'a str>,
+    /// Byte offsets in the module, including the trailing blank lines.
+    pub span: Range<usize>,
+    /// Line number of the start, counted from 1.
+    pub line: usize,
+    pub text: &'a str,
+}
+
+/// What is being skipped over while looking for the declarations.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum State {
+    Code,
+    BlockComment(usize),
+    String,
+    MultilineString,
+    Char,
+    Glsl,
+}
+
+/// Walk over the text, calling back with the byte offset and the state at
+/// each step, i.e. at each character outside of line comments and literal
+/// delimiters.
+fn scan(text: &str, mut visit: impl FnMut(usize, State)) {
+    let mut state = State::Code;
+    let mut i = 0;
+
+    while i < text.len() {
+        let rest = &text[i..];
+        visit(i, state);
+
+        let (next, skip) = match state {
+            State::Code if rest.starts_with("--") => {
+                (State::Code, rest.find('\n').unwrap_or(rest.len()))
+            }
+            State::Code if rest.starts_with("{-") => (State::BlockComment(1), 2),
+            State::Code if rest.starts_with("\"\"\"") => (State::MultilineString, 3),
+            State::Code if rest.starts_with('"') => (State::String, 1),
+            State::Code if rest.starts_with('\'') => (State::Char, 1),
+            State::Code if rest.starts_with("[glsl|") => (State::Glsl, 6),
+
+            State::BlockComment(depth) if rest.starts_with("{-") => {
+                (State::BlockComment(depth + 1), 2)
+            }
+            State::BlockComment(1) if rest.starts_with("-}") => (State::Code, 2),
+            State::BlockComment(depth) if rest.starts_with("-}") => {
+                (State::BlockComment(depth - 1), 2)
+            }
+
+            State::String | State::MultilineString | State::Char if rest.starts_with('\\') => (
+                state,
+                1 + rest[1..].chars().next().map_or(0, char::len_utf8),
+            ),
+            State::MultilineString if rest.starts_with("\"\"\"") => (State::Code, 3),
+            State::String if rest.starts_with('"') => (State::Code, 1),
+            State::Char if rest.starts_with('\'') => (State::Code, 1),
+            // Unterminated, recover at the end of the line
+            State::String | State::Char if rest.starts_with('\n') => (State::Code, 1),
+            State::Glsl if rest.starts_with("|]") => (State::Code, 2),
+
+            _ => (state, rest.chars().next().map_or(1, char::len_utf8)),
+        };
+
+        state = next;
+        i += skip.max(1);
+    }
+}
+
+/// Byte offsets of the lines starting a top-level declaration.
+///
+/// A comment at column 0 only starts a declaration if what follows it does
+/// too, or if code follows it on the same line, e.g. `{- TODO -} type Msg`.
+/// Otherwise it sits in the middle of the previous one, e.g. commenting out
+/// a line of a function body, and stays with it.
+fn starts(text: &str) -> Vec<usize> {
+    let bytes = text.as_bytes();
+    let mut starts = vec![];
+    let mut comments = vec![];
+
+    scan(text, |i, state| {
+        let rest = &text[i..];
+        let line_start = i == 0 || bytes[i - 1] == b'\n';
+        let comment = rest.starts_with("--") || rest.starts_with("{-");
+        if state == State::Code && !bytes[i].is_ascii_whitespace() {
+            let line = text[..i].rfind('\n').map_or(0, |n| n + 1);
+            if line_start && comment {
+                comments.push(i);
+            } else if line_start || !comment && comments.last() == Some(&line) {
+                starts.append(&mut comments);
+                starts.push(line);
+                starts.dedup();
+            } else if !comment {
+                comments.clear();
+            }
+        }
+    });
+
+    starts.append(&mut comments);
+    starts
+}
+
+/// Numbers of the lines, counted from 1, that start inside a block comment,
+/// a multi-line string or a GLSL block, where they mean nothing to Elm.
+pub fn literal_lines(text: &str) -> Vec<usize> {
+    let mut lines = vec![];
+    let mut line = 1;
+    let mut counted = 0;
+
+    scan(text, |i, state| {
+        if i > 0 && text.as_bytes()[i - 1] == b'\n' {
+            line += text[counted..i].matches('\n').count();
+            counted = i;
+            if state != State::Code {
+                lines.push(line);
+            }
+        }
+    });
+    lines
+}
+
+/// A name, keyword, symbol or literal of the code, along with how deep it is
+/// nested in brackets.
+#[derive(Debug, Clone)]
+pub struct Token<'a> {
+    pub span: Range<usize>,
+    pub text: &'a str,
+    pub depth: usize,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Class {
+    Name,
+    Symbol,
+    Bracket,
+    Literal,
+}
+
+/// Split the code into its tokens, leaving out whitespace and comments. A
+/// qualified name or a field access, e.g. `Cmd.none` or `model.count`, is a
+/// single token.
+pub fn tokens(text: &str) -> Vec<Token<'_>> {
+    let mut visits: Vec<(usize, Option<Class>)> = vec![];
+    scan(text, |i, state| {
+        let rest = &text[i..];
+        let c = rest.chars().next().unwrap_or_default();
+        let class = match state {
+            State::Code if rest.starts_with("--") || rest.starts_with("{-") => None,
+            State::Code if c.is_whitespace() => None,
+            State::Code if rest.starts_with(['"', '\'']) || rest.starts_with("[glsl|") => {
+                Some(Class::Literal)
+            }
+            State::Code if is_name_char(c) || c == '.' && rest[1..].starts_with(is_name_char) => {
+                Some(Class::Name)
+            }
+            State::Code if "()[]{},".contains(c) => Some(Class::Bracket),
+            State::Code => Some(Class::Symbol),
+            State::BlockComment(_) => None,
+            _ => Some(Class::Literal),
+        };
+        visits.push((i, class));
+    });
+
+    let mut tokens: Vec<Token> = vec![];
+    let mut depth = 0;
+    for (n, &(start, class)) in visits.iter().enumerate() {
+        let end = visits.get(n + 1).map_or(text.len(), |v| v.0);
+        let Some(class) = class else {
+            continue;
+        };
+
+        let previous = n.checked_sub(1).and_then(|p| visits[p].1);
+        let continues = previous == Some(class) && class != Class::Bracket;
+        match tokens.last_mut() {
+            Some(token) if continues && token.span.end == start => {
+                token.span.end = end;
+                token.text = &text[token.span.clone()];
+            }
+            _ => {
+                let text = &text[start..end];
+                if text.starts_with([')', ']', '}']) {
+                    depth = usize::saturating_sub(depth, 1);
+                }
+                tokens.push(Token {
+                    span: start..end,
+                    text,
+                    depth,
+                });
+                if text.starts_with(['(', '[', '{']) {
+                    depth += 1;
+                }
+            }
+        }
+    }
+    tokens
+}
+
+/// Byte ranges of the parenthesized code, from the `(` to the `)`, in the
+/// order they open.
+pub fn parens(text: &str) -> Vec<Range<usize>> {
+    let mut open = vec![];
+    let mut parens = vec![];
+    scan(text, |i, state| match text.as_bytes()[i] {
+        b'(' if state == State::Code => open.push(i),
+        b')' if state == State::Code => parens.extend(open.pop().map(|start| start..i + 1)),
+        _ => {}
+    });
+    parens.sort_by_key(|r| r.start);
+    parens
+}
+
+/// Byte ranges of the code between the separators that aren't nested in
+/// brackets, e.g. the elements of a tuple.
+pub fn split(text: &str, separator: char) -> Vec<Range<usize>> {
+    let mut parts = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    scan(text, |i, state| {
+        let rest = &text[i..];
+        if state != State::Code || rest.starts_with("{-") {
+            return;
+        }
+        match rest.chars().next() {
+            Some('(' | '[' | '{') => depth += 1,
+            Some(')' | ']' | '}') => depth -= 1,
+            Some(c) if depth == 0 && c == separator => {
+                parts.push(start..i);
+                start = i + c.len_utf8();
+            }
+            _ => {}
+        }
+    });
+    parts.push(start..text.len());
+    parts
+}
+
+/// The text from the first code after the comments it starts with.
+fn code_after_comments(text: &str) -> &str {
+    let mut start = None;
+    scan(text, |i, state| {
+        let rest = &text[i..];
+        if start.is_none()
+            && state == State::Code
+            && !rest.starts_with(char::is_whitespace)
+            && !rest.starts_with("--")
+            && !rest.starts_with("{-")
+        {
+            start = Some(i);
+        }
+    });
+    &text[start.unwrap_or(text.len())..]
+}
+
+fn is_name_start(c: char) -> bool {
+    c.is_lowercase() || c == '_'
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// The kind of the declaration, and the name of the value it annotates or
+/// defines.
+fn classify(text: &str) -> (Kind, Option<&str>) {
+    let first = text.split_whitespace().next().unwrap_or_default();
+
+    if first.starts_with("--") || first.starts_with("{-") {
+        // Comments in front of a type declaration on its line
+        let code = code_after_comments(text);
+        if !text[..text.len() - code.len()].contains('\n') && classify(code).0 == Kind::Type {
+            return (Kind::Type, None);
+        }
+        return (Kind::Comment, None);
+    }
+
+    match first {
+        "module" => return (Kind::Module, None),
+        "port" | "effect" if text.split_whitespace().nth(1) == Some("module") => {
+            return (Kind::Module, None)
+        }
+        "import" => return (Kind::Import, None),
+        "type" => return (Kind::Type, None),
+        "port" => return (Kind::Port, None),
+        "infix" => return (Kind::Infix, None),
+        _ => {}
+    }
+
+    if !text.starts_with(is_name_start) {
+        return (Kind::Other, None);
+    }
+
+    let end = text.find(|c| !is_name_char(c)).unwrap_or(text.len());
+    let (name, rest) = text.split_at(end);
+    if rest.trim_start().starts_with(':') {
+        (Kind::Annotation, Some(name))
+    } else if rest.contains('=') {
+        (Kind::Value, Some(name))
+    } else {
+        (Kind::Other, None)
+    }
+}
+
+/// Split the module into its top-level declarations.
+pub fn parse(text: &str) -> Vec<Decl<'_>> {
+    let mut starts = starts(text);
+    if starts.first() != Some(&0) {
+        starts.insert(0, 0);
+    }
+
+    let mut line = 1;
+    let mut decls = vec![];
+    for (i, &start) in starts.iter().enumerate() {
+        let end = starts.get(i + 1).copied().unwrap_or(text.len());
+        if start == end {
+            continue;
+        }
+
+        let slice = &text[start..end];
+        let (kind, name) = if slice.trim().is_empty() {
+            (Kind::Blank, None)
+        } else {
+            classify(slice)
+        };
+
+        decls.push(Decl {
+            kind,
+            name,
+            span: start..end,
+            line,
+            text: slice,
+        });
+        line += slice.matches('\n').count();
+    }
+    decls
+}
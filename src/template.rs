@@ -0,0 +1,392 @@
+use indoc::indoc;
+
+fn _fmt_defs(shared: bool, request: bool, user: bool) -> (String, String, String, String) {
+    let (mut s_sig, mut s_arg) = if shared {
+        ("Shared.Model ->".to_string(), "shared".to_string())
+    } else {
+        (String::new(), String::new())
+    };
+
+    // The user comes first, ahead of the shared model
+    if user {
+        s_sig = format!("Auth.User -> {}", s_sig).trim_end().to_string();
+        s_arg = format!("user {}", s_arg).trim_end().to_string();
+    }
+
+    let (r_sig, r_arg) = if request {
+        ("Request.With Params ->", "req")
+    } else {
+        ("", "")
+    };
+
+    (s_sig, r_sig.into(), s_arg, r_arg.into())
+}
+
+/// Wrap the fields of the page in the `\user -> ...` a protected page
+/// passes the signed in user to.
+fn _fmt_protected(template: &str) -> String {
+    let mut lines = vec![];
+    for line in template.lines() {
+        match line.trim_start() {
+            l if l.starts_with("Page.") => {
+                lines.push(line.replacen("Page.", "Page.protected.", 1));
+                lines.push("        (\\user ->".into());
+            }
+            l if l.starts_with(['{', ',']) => lines.push(format!("    {}", line)),
+            "}" => {
+                lines.push(format!("    {}", line));
+                lines.push("        )".into());
+            }
+            _ => lines.push(line.into()),
+        }
+    }
+    lines.join("\n") + "\n"
+}
+
+fn _fmt_branches(messages: &[(String, usize)], result: &str) -> String {
+    if messages.is_empty() {
+        return format!("        _ ->\n            {}", result);
+    }
+
+    messages
+        .iter()
+        .map(|(name, args)| {
+            let pattern: Vec<&str> = std::iter::once(name.as_str())
+                .chain(std::iter::repeat_n("_", *args))
+                .collect();
+            format!("        {} ->\n            {}", pattern.join(" "), result)
+        })
+        .collect::<Vec<String>>()
+        .join("\n\n")
+}
+
+/// The template with a comment right below its definition showing how to
+/// read the params of a dynamic route from the request, e.g. `req.params.id`,
+/// if it takes the request.
+pub fn with_params(template: String, params: &[String]) -> String {
+    if params.is_empty() {
+        return template;
+    }
+
+    let example = params
+        .iter()
+        .map(|p| format!("req.params.{}", p))
+        .collect::<Vec<String>>()
+        .join(", ");
+
+    let mut lines = vec![];
+    for line in template.lines() {
+        lines.push(line.to_string());
+        if !line.starts_with(' ')
+            && line.trim_end().ends_with('=')
+            && line.split_whitespace().any(|w| w == "req")
+        {
+            lines.push(format!("    -- The params of the route: {}", example));
+        }
+    }
+    lines.join("\n") + "\n"
+}
+
+/// The init template with an example of reading a query parameter of the
+/// request, e.g. `?search=elm`, right below its definition.
+pub fn with_query(template: String) -> String {
+    let mut lines = vec![];
+    let mut body = false;
+    for line in template.lines() {
+        if body && !line.is_empty() {
+            lines.push("    in".into());
+            body = false;
+        }
+        lines.push(line.to_string());
+        if !line.starts_with(' ')
+            && line.trim_end().ends_with('=')
+            && line.split_whitespace().any(|w| w == "req")
+        {
+            lines.extend(
+                [
+                    "    let",
+                    "        search =",
+                    "            req.query",
+                    "                |> Dict.get \"search\"",
+                    "                |> Maybe.withDefault \"\"",
+                ]
+                .iter()
+                .map(|l| l.to_string()),
+            );
+            body = true;
+        }
+    }
+    lines.join("\n") + "\n"
+}
+
+/// The page types, from the simplest to the most capable.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum PageType {
+    Static,
+    Sandbox,
+    Element,
+    Advanced,
+}
+
+impl PageType {
+    pub fn from(string: &str) -> Option<Self> {
+        match string {
+            "static" => Some(Self::Static),
+            "sandbox" => Some(Self::Sandbox),
+            "element" => Some(Self::Element),
+            "advanced" => Some(Self::Advanced),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PageType::Static => "static",
+            PageType::Sandbox => "sandbox",
+            PageType::Element => "element",
+            PageType::Advanced => "advanced",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            PageType::Static => "A page with only a view and no state of its own",
+            PageType::Sandbox => "A page with local state updated without side effects",
+            PageType::Element => "A page with local state, commands and subscriptions",
+            PageType::Advanced => "An element page whose update can send shared effects",
+        }
+    }
+
+    pub fn exposing_template(self) -> &'static str {
+        match self {
+            PageType::Static => "page",
+            PageType::Sandbox => "page, Model, Msg",
+            PageType::Element => "page, Model, Msg",
+            PageType::Advanced => "page, Model, Msg",
+        }
+    }
+
+    /// The page function, protected with `Page.protected` if need be.
+    pub fn page_template(self, shared: bool, request: bool, user: bool, protected: bool) -> String {
+        let (_, _, s_arg, r_arg) = _fmt_defs(shared, request, user);
+        let template = match self {
+            PageType::Static => format!(
+                indoc! {r###"
+                page : Shared.Model -> Request.With Params -> Page
+                page shared req =
+                    Page.static
+                        {{ view = view {s_arg} {r_arg}
+                        }}
+                "###},
+                s_arg = s_arg,
+                r_arg = r_arg
+            ),
+
+            PageType::Sandbox => format!(
+                indoc! {r###"
+                page : Shared.Model -> Request.With Params -> Page.With Model Msg
+                page shared req =
+                    Page.sandbox
+                        {{ init = init {s_arg} {r_arg}
+                        , update = update {s_arg} {r_arg}
+                        , view = view {s_arg} {r_arg}
+                        }}
+                "###},
+                s_arg = s_arg,
+                r_arg = r_arg
+            ),
+
+            PageType::Element => format!(
+                indoc! {r###"
+                page : Shared.Model -> Request.With Params -> Page.With Model Msg
+                page shared req =
+                    Page.element
+                        {{ init = init {s_arg} {r_arg}
+                        , update = update {s_arg} {r_arg}
+                        , view = view {s_arg} {r_arg}
+                        , subscriptions = subscriptions {s_arg} {r_arg}
+                        }}
+                "###},
+                s_arg = s_arg,
+                r_arg = r_arg
+            ),
+
+            PageType::Advanced => format!(
+                indoc! {r###"
+                page : Shared.Model -> Request.With Params -> Page.With Model Msg
+                page shared req =
+                    Page.advanced
+                        {{ init = init {s_arg} {r_arg}
+                        , update = update {s_arg} {r_arg}
+                        , view = view {s_arg} {r_arg}
+                        , subscriptions = subscriptions {s_arg} {r_arg}
+                        }}
+                "###},
+                s_arg = s_arg,
+                r_arg = r_arg
+            ),
+        };
+
+        if protected {
+            _fmt_protected(&template)
+        } else {
+            template
+        }
+    }
+
+    /// The init template, starting with the given model, e.g. `{}`.
+    pub fn init_template(self, shared: bool, request: bool, user: bool, model: &str) -> String {
+        let (s_sig, r_sig, s_arg, r_arg) = _fmt_defs(shared, request, user);
+
+        match self {
+            PageType::Static => "".into(),
+
+            PageType::Sandbox => format!(
+                indoc! {r###"
+                init : {s_sig} {r_sig} Model
+                init {s_arg} {r_arg} =
+                    {model}
+                "###},
+                model = model,
+                s_sig = s_sig,
+                s_arg = s_arg,
+                r_sig = r_sig,
+                r_arg = r_arg
+            ),
+
+            PageType::Element => format!(
+                indoc! {r###"
+                init : {s_sig} {r_sig} (Model, Cmd Msg)
+                init {s_arg} {r_arg} =
+                    ({model}, Cmd.none)
+                "###},
+                model = model,
+                s_sig = s_sig,
+                s_arg = s_arg,
+                r_sig = r_sig,
+                r_arg = r_arg
+            ),
+
+            PageType::Advanced => format!(
+                indoc! {r###"
+                init : {s_sig} {r_sig} (Model, Effect Msg)
+                init {s_arg} {r_arg} =
+                    ({model}, Effect.none)
+                "###},
+                model = model,
+                s_sig = s_sig,
+                s_arg = s_arg,
+                r_sig = r_sig,
+                r_arg = r_arg
+            ),
+        }
+    }
+
+    /// The update function, with a branch for each of the constructors of the
+    /// page's Msg type, or a single catch-all branch if it has none.
+    pub fn update_template(
+        self,
+        shared: bool,
+        request: bool,
+        user: bool,
+        messages: &[(String, usize)],
+    ) -> String {
+        let (s_sig, r_sig, s_arg, r_arg) = _fmt_defs(shared, request, user);
+
+        match self {
+            PageType::Static => "".into(),
+
+            PageType::Sandbox => format!(
+                indoc! {r###"
+                update : {s_sig} {r_sig} Msg -> Model -> Model
+                update {s_arg} {r_arg} msg model =
+                    case msg of
+                {branches}
+                "###},
+                s_sig = s_sig,
+                s_arg = s_arg,
+                r_sig = r_sig,
+                r_arg = r_arg,
+                branches = _fmt_branches(messages, "model")
+            ),
+
+            PageType::Element => format!(
+                indoc! {r###"
+                update : {s_sig} {r_sig} Msg -> Model -> ( Model, Cmd Msg )
+                update {s_arg} {r_arg} msg model =
+                    case msg of
+                {branches}
+                "###},
+                s_sig = s_sig,
+                s_arg = s_arg,
+                r_sig = r_sig,
+                r_arg = r_arg,
+                branches = _fmt_branches(messages, "( model, Cmd.none )")
+            ),
+
+            PageType::Advanced => format!(
+                indoc! {r###"
+                update : {s_sig} {r_sig} Msg -> Model -> ( Model, Effect Msg )
+                update {s_arg} {r_arg} msg model =
+                    case msg of
+                {branches}
+                "###},
+                s_sig = s_sig,
+                s_arg = s_arg,
+                r_sig = r_sig,
+                r_arg = r_arg,
+                branches = _fmt_branches(messages, "( model, Effect.none )")
+            ),
+        }
+    }
+
+    pub fn view_template(self, shared: bool, request: bool, user: bool) -> String {
+        let (s_sig, r_sig, s_arg, r_arg) = _fmt_defs(shared, request, user);
+
+        match self {
+            PageType::Static => format!(
+                indoc! {r###"
+                view : {s_sig} {r_sig} View msg
+                view {s_arg} {r_arg} =
+                    View.placeholder "Hello World"
+                "###},
+                s_sig = s_sig,
+                s_arg = s_arg,
+                r_sig = r_sig,
+                r_arg = r_arg
+            ),
+
+            PageType::Sandbox | PageType::Element | PageType::Advanced => format!(
+                indoc! {r###"
+                view : {s_sig} {r_sig} Model -> View Msg
+                view {s_arg} {r_arg} model =
+                    View.placeholder "Hello World"
+                "###},
+                s_sig = s_sig,
+                s_arg = s_arg,
+                r_sig = r_sig,
+                r_arg = r_arg
+            ),
+        }
+    }
+
+    pub fn subscriptions_template(self, shared: bool, request: bool, user: bool) -> String {
+        let (s_sig, r_sig, s_arg, r_arg) = _fmt_defs(shared, request, user);
+
+        match self {
+            PageType::Static | PageType::Sandbox => "".into(),
+
+            PageType::Element | PageType::Advanced => format!(
+                indoc! {r###"
+                subscriptions : {s_sig} {r_sig} Model -> Sub Msg
+                subscriptions {s_arg} {r_arg} model =
+                    Sub.none
+                "###},
+                s_sig = s_sig,
+                s_arg = s_arg,
+                r_sig = r_sig,
+                r_arg = r_arg
+            ),
+        }
+    }
+}
@@ -0,0 +1,255 @@
+use crate::cli::Cli;
+use crate::page::Page;
+use crate::template::PageType;
+use anyhow::Result;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style, Stylize};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use std::path::PathBuf;
+
+const TYPES: [PageType; 4] = [
+    PageType::Static,
+    PageType::Sandbox,
+    PageType::Element,
+    PageType::Advanced,
+];
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Status {
+    Pending,
+    Applied,
+    Skipped,
+    Failed,
+}
+
+impl Status {
+    fn marker(self) -> &'static str {
+        match self {
+            Self::Pending => " ",
+            Self::Applied => "✓",
+            Self::Skipped => "-",
+            Self::Failed => "!",
+        }
+    }
+}
+
+struct Entry {
+    path: PathBuf,
+    name: String,
+    kind: String,
+    page: Result<Page>,
+    status: Status,
+}
+
+struct App {
+    entries: Vec<Entry>,
+    list: ListState,
+    template: PageType,
+    shared: bool,
+    request: bool,
+    scroll: u16,
+    message: String,
+}
+
+impl App {
+    fn selected(&self) -> Option<&Entry> {
+        self.list.selected().and_then(|i| self.entries.get(i))
+    }
+
+    fn preview(&self) -> String {
+        match self.selected().map(|e| &e.page) {
+            Some(Ok(page)) => page
+                .clone()
+                .to(self.template, self.shared, self.request)
+                .to_string(),
+            Some(Err(e)) => format!("error: {}", e),
+            None => "No pages found".into(),
+        }
+    }
+
+    fn select(&mut self, offset: isize) {
+        if self.entries.is_empty() {
+            return;
+        }
+        let last = self.entries.len() as isize - 1;
+        let current = self.list.selected().unwrap_or_default() as isize;
+        self.list
+            .select(Some((current + offset).clamp(0, last) as usize));
+        self.scroll = 0;
+    }
+
+    fn mark(&mut self, status: Status, message: String) {
+        if let Some(entry) = self.list.selected().and_then(|i| self.entries.get_mut(i)) {
+            entry.status = status;
+        }
+        self.message = message;
+        self.select(1);
+    }
+
+    fn apply(&mut self, cli: &Cli) {
+        let result = match self.selected() {
+            Some(Entry {
+                path,
+                page: Ok(page),
+                ..
+            }) => {
+                let page = page.clone().to(self.template, self.shared, self.request);
+                crate::writable(cli, path, true)
+                    .and_then(|_| crate::backup(cli, path))
+                    .and_then(|_| crate::write(path, &page))
+                    .map(|_| match page.old_code() {
+                        Some(_) => format!(
+                            "{}, the replaced code is in {}",
+                            path.display(),
+                            crate::sidecar(path).display()
+                        ),
+                        None => path.display().to_string(),
+                    })
+            }
+            _ => return,
+        };
+
+        match result {
+            Ok(path) => self.mark(Status::Applied, format!("migrated {}", path)),
+            Err(e) => self.mark(Status::Failed, format!("error: {}", e)),
+        }
+    }
+
+    fn skip(&mut self) {
+        if let Some(path) = self.selected().map(|e| e.path.display().to_string()) {
+            self.mark(Status::Skipped, format!("skipped {}", path));
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [main, status, help] = Layout::vertical([
+            Constraint::Min(1),
+            Constraint::Length(1),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        let [left, right] =
+            Layout::horizontal([Constraint::Percentage(35), Constraint::Percentage(65)])
+                .areas(main);
+
+        let items: Vec<ListItem> = self
+            .entries
+            .iter()
+            .map(|e| ListItem::new(format!("[{}] {} ({})", e.status.marker(), e.name, e.kind)))
+            .collect();
+
+        let list = List::new(items)
+            .block(Block::bordered().title(" Pages "))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(list, left, &mut self.list);
+
+        let mut target = self.template.name().to_string();
+        if self.shared {
+            target.push_str(" -s");
+        }
+        if self.request {
+            target.push_str(" -r");
+        }
+
+        let preview = Paragraph::new(self.preview())
+            .block(Block::bordered().title(format!(" Preview: {} ", target)))
+            .scroll((self.scroll, 0));
+        frame.render_widget(preview, right);
+
+        frame.render_widget(Line::from(self.message.as_str()), status);
+        frame.render_widget(
+            Line::from(
+                "j/k: move  a: apply  x: skip  1-4: template  s/r: shared/request  \
+                 PgUp/PgDn: scroll  q: quit",
+            )
+            .dim(),
+            help,
+        );
+    }
+
+    fn run(&mut self, cli: &Cli, terminal: &mut DefaultTerminal) -> Result<()> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+
+            let key = match event::read()? {
+                Event::Key(key) if key.kind == KeyEventKind::Press => key,
+                _ => continue,
+            };
+
+            match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                KeyCode::Char('j') | KeyCode::Down => self.select(1),
+                KeyCode::Char('k') | KeyCode::Up => self.select(-1),
+                KeyCode::Char('a') | KeyCode::Enter => self.apply(cli),
+                KeyCode::Char('x') => self.skip(),
+                KeyCode::Char('s') => self.shared = !self.shared,
+                KeyCode::Char('r') => self.request = !self.request,
+                KeyCode::Char(c @ '1'..='4') => {
+                    self.template = TYPES[c as usize - '1' as usize];
+                }
+                KeyCode::PageDown => self.scroll = self.scroll.saturating_add(10),
+                KeyCode::PageUp => self.scroll = self.scroll.saturating_sub(10),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Browse the pages and migrate them one by one in a full screen terminal UI.
+pub fn run(cli: &Cli, files: Vec<PathBuf>) -> Result<()> {
+    let entries = files
+        .into_iter()
+        .map(|path| {
+            let page = crate::load(cli, &path).map(|(_, page)| page);
+            let (name, kind) = match &page {
+                Ok(page) => (
+                    page.module_name()
+                        .map(String::from)
+                        .unwrap_or_else(|| path.display().to_string()),
+                    page.shape()
+                        .map(|s| s.pagetype.name())
+                        .unwrap_or("-")
+                        .to_string(),
+                ),
+                Err(_) => (path.display().to_string(), "error".into()),
+            };
+            Entry {
+                path,
+                name,
+                kind,
+                page,
+                status: Status::Pending,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let mut list = ListState::default();
+    if !entries.is_empty() {
+        list.select(Some(0));
+    }
+
+    let mut app = App {
+        entries,
+        list,
+        template: cli.template.unwrap_or(PageType::Element),
+        shared: cli.shared,
+        request: cli.request,
+        scroll: 0,
+        message: String::new(),
+    };
+
+    // Logging to stderr would mess up the screen
+    let level = log::max_level();
+    log::set_max_level(log::LevelFilter::Off);
+
+    let mut terminal = ratatui::try_init()?;
+    let result = app.run(cli, &mut terminal);
+    ratatui::restore();
+
+    log::set_max_level(level);
+    result
+}